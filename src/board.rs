@@ -0,0 +1,842 @@
+//! Pieces, squares, moves and the board itself, with making and taking
+//! back moves and the draw rules.
+
+use crate::notation::{piece_to_char, promotion_name};
+use crate::movegen::PAWN_ATTACKS;
+use crate::zobrist::{zobrist_piece, POLYGLOT_RANDOM, ZOBRIST_WHITE_TO_MOVE};
+use std::fmt;
+
+/// The two sides
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PieceColor {
+    White,
+    Black,
+}
+
+impl PieceColor {
+    pub fn opposite(self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
+
+/// Kinds of pieces, in the order used to index per-type tables
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PieceType {
+    King,
+    Queen,
+    Rook,
+    Bishop,
+    Knight,
+    Pawn,
+}
+
+impl PieceType {
+    /// Every piece type, in discriminant order
+    pub const ALL: [PieceType; 6] = [
+        PieceType::King,
+        PieceType::Queen,
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Pawn,
+    ];
+}
+
+/// A piece of a given type and color
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Piece {
+    pub piece_type: PieceType,
+    pub color: PieceColor,
+}
+
+/// Squares are numbered from a1 = 0 to h8 = 63, rank by rank
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Square(pub(crate) u8);
+
+impl Square {
+    /// The square on `file` and `rank`, both counted from 0
+    pub fn new(file: usize, rank: usize) -> Square {
+        Square((rank * 8 + file) as u8)
+    }
+
+    pub fn file(self) -> usize {
+        self.0 as usize % 8
+    }
+
+    pub fn rank(self) -> usize {
+        self.0 as usize / 8
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn bit(self) -> Bitboard {
+        1 << self.0
+    }
+
+    /// Parses coordinates such as "e4"
+    pub fn parse(s: &str) -> Option<Square> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+
+        let (file, rank) = (bytes[0], bytes[1]);
+        if !(b'a'..=b'h').contains(&file) || !(b'1'..=b'8').contains(&rank) {
+            return None;
+        }
+
+        Some(Square::new((file - b'a') as usize, (rank - b'1') as usize))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file() as u8) as char, self.rank() + 1)
+    }
+}
+
+/// What a move does besides moving a piece
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveKind {
+    Quiet,
+    DoublePawnPush,
+    Capture,
+    EnPassant,
+    Castle,
+}
+
+/// A move from one square to another; castling goes from the king to its rook
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+    pub kind: MoveKind,
+}
+
+impl Move {
+    // Castling is stored as the king capturing its own rook, which works the
+    // same for Chess960; this gives the squares the king and rook end up on
+    pub fn castling_destinations(&self) -> (Square, Square) {
+        let rank = self.from.rank();
+        if self.to.file() > self.from.file() {
+            (Square::new(6, rank), Square::new(5, rank))
+        } else {
+            (Square::new(2, rank), Square::new(3, rank))
+        }
+    }
+
+    /// Whether the move takes an enemy piece, en passant included
+    pub fn is_capture(&self) -> bool {
+        matches!(self.kind, MoveKind::Capture | MoveKind::EnPassant)
+    }
+}
+
+// Coordinate notation, e.g. "e2e4" or "e7e8q"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Castling is written as the king's move, as in standard chess UCI,
+        // unless in Chess960 that would be no move at all or look like a
+        // one-square king move; then it is the king taking its rook
+        let to = match self.kind {
+            MoveKind::Castle => {
+                let king_to = self.castling_destinations().0;
+                if self.from.file().abs_diff(king_to.file()) >= 2 {
+                    king_to
+                } else {
+                    self.to
+                }
+            }
+            _ => self.to,
+        };
+        write!(f, "{}{}", self.from, to)?;
+        if let Some(piece_type) = self.promotion {
+            write!(f, "{}", piece_to_char(Piece { piece_type, color: PieceColor::Black }))?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of squares, one bit per square in the same order as Square
+pub type Bitboard = u64;
+
+pub(crate) const RANK_1: Bitboard = 0xff;
+pub(crate) const RANK_8: Bitboard = RANK_1 << 56;
+const LIGHT_SQUARES: Bitboard = 0x55aa_55aa_55aa_55aa;
+
+/// The squares in a bitboard, from a1 towards h8
+pub fn bitboard_squares(mut bitboard: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = Square(bitboard.trailing_zeros() as u8);
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+/// Each right remembers the file of the rook it castles with: the a- and
+/// h-file in standard chess, wherever the rooks start in Chess960
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CastlingRights {
+    pub white_kingside: Option<usize>,
+    pub white_queenside: Option<usize>,
+    pub black_kingside: Option<usize>,
+    pub black_queenside: Option<usize>,
+}
+
+impl CastlingRights {
+    /// No castling rights at all
+    pub fn none() -> CastlingRights {
+        CastlingRights {
+            white_kingside: None,
+            white_queenside: None,
+            black_kingside: None,
+            black_queenside: None,
+        }
+    }
+
+    /// All four rights of the standard start position
+    pub fn all() -> CastlingRights {
+        CastlingRights {
+            white_kingside: Some(7),
+            white_queenside: Some(0),
+            black_kingside: Some(7),
+            black_queenside: Some(0),
+        }
+    }
+
+    pub fn has(&self, color: PieceColor, kingside: bool) -> bool {
+        self.rook_file(color, kingside).is_some()
+    }
+
+    /// File of the rook the given right castles with, if the right still exists
+    pub fn rook_file(&self, color: PieceColor, kingside: bool) -> Option<usize> {
+        match (color, kingside) {
+            (PieceColor::White, true) => self.white_kingside,
+            (PieceColor::White, false) => self.white_queenside,
+            (PieceColor::Black, true) => self.black_kingside,
+            (PieceColor::Black, false) => self.black_queenside,
+        }
+    }
+
+    pub(crate) fn rook_file_mut(&mut self, color: PieceColor, kingside: bool) -> &mut Option<usize> {
+        match (color, kingside) {
+            (PieceColor::White, true) => &mut self.white_kingside,
+            (PieceColor::White, false) => &mut self.white_queenside,
+            (PieceColor::Black, true) => &mut self.black_kingside,
+            (PieceColor::Black, false) => &mut self.black_queenside,
+        }
+    }
+}
+
+/// Why a game is, or may be declared, drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DrawReason {
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+}
+
+impl DrawReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::FivefoldRepetition => "fivefold repetition",
+            DrawReason::FiftyMoveRule => "fifty-move rule",
+            DrawReason::SeventyFiveMoveRule => "seventy-five-move rule",
+            DrawReason::InsufficientMaterial => "insufficient material",
+        }
+    }
+}
+
+/// Why a move cannot be played
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveError {
+    /// The input could not be read as a move at all
+    BadSyntax,
+    NoPiece(Square),
+    OpponentPiece(Square),
+    /// The piece does not move that way, whatever stands in between
+    IllegalPieceMove(PieceType),
+    PathBlocked(Square),
+    OwnPieceOnTarget(Square),
+    /// The king is in check and the move does not get it out of check
+    StillInCheck,
+    LeavesKingInCheck,
+    CastlingRightsLost,
+    CastlingThroughCheck,
+    InvalidPromotion,
+    /// A SAN move that more than one piece of this type could play
+    AmbiguousMove(PieceType, Square),
+    /// A SAN move that no piece of this type can play
+    NoMatchingMove(PieceType, Square),
+    NothingToCapture(Square),
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::BadSyntax => {
+                write!(f, "use a move like 'e4', 'Nf3', 'exd5', 'O-O' or 'e8=Q', or 'from to' like 'e2 e4' or 'e7 e8 n'")
+            }
+            MoveError::NoPiece(square) => write!(f, "there is no piece on {}", square),
+            MoveError::OpponentPiece(square) => write!(f, "the piece on {} belongs to your opponent", square),
+            MoveError::IllegalPieceMove(piece_type) => {
+                write!(f, "a {} can't move like that", promotion_name(*piece_type).to_lowercase())
+            }
+            MoveError::PathBlocked(square) => write!(f, "the way is blocked by the piece on {}", square),
+            MoveError::OwnPieceOnTarget(square) => write!(f, "you can't capture your own piece on {}", square),
+            MoveError::StillInCheck => write!(f, "your king is in check and that move doesn't get it out of check"),
+            MoveError::LeavesKingInCheck => write!(f, "that would leave your king in check"),
+            MoveError::CastlingRightsLost => write!(f, "castling on that side is no longer allowed, as the king or rook has moved"),
+            MoveError::CastlingThroughCheck => write!(f, "the king can't castle out of, through or into check"),
+            MoveError::InvalidPromotion => {
+                write!(f, "only a pawn reaching the last rank promotes, to a queen, rook, bishop or knight")
+            }
+            MoveError::AmbiguousMove(piece_type, square) => write!(
+                f,
+                "more than one {} can move to {}; add the file or rank of the one you mean, e.g. 'Nbd2'",
+                promotion_name(*piece_type).to_lowercase(),
+                square
+            ),
+            MoveError::NoMatchingMove(piece_type, square) => {
+                write!(f, "no {} can move to {}", promotion_name(*piece_type).to_lowercase(), square)
+            }
+            MoveError::NothingToCapture(square) => write!(f, "there is nothing to capture on {}", square),
+            MoveError::GameOver => write!(f, "the game is over"),
+        }
+    }
+}
+
+/// Everything make_move changes that cannot be recomputed when taking the move back
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Undo {
+    mv: Move,
+    captured: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    zobrist: u64,
+}
+
+impl Undo {
+    /// The move as it was played, with a missing promotion filled in
+    pub fn played_move(&self) -> Move {
+        self.mv
+    }
+}
+
+/// A chess position together with the history needed for the draw rules
+#[derive(Clone)]
+pub struct Board {
+    // One bitboard per piece type and one per color; a piece stands on a
+    // square when the square is set in both its type and its color set
+    pub(crate) pieces: [Bitboard; 6],
+    pub(crate) colors: [Bitboard; 2],
+    pub(crate) castling_rights: CastlingRights,
+    // Square skipped over by a double pawn push on the previous move
+    pub(crate) en_passant: Option<Square>,
+    pub(crate) side_to_move: PieceColor,
+    // Half-moves since the last capture or pawn move
+    pub(crate) halfmove_clock: u32,
+    // Starts at 1 and increases after each Black move
+    pub(crate) fullmove_number: u32,
+    // Polyglot-compatible hash of the position, updated as pieces move
+    pub(crate) zobrist: u64,
+    // Repetition keys of every position reached so far, including the current one
+    pub(crate) history: Vec<u64>,
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+impl Board {
+    /// The standard start position
+    pub fn new() -> Board {
+        let mut board = Board::empty();
+        board.castling_rights = CastlingRights::all();
+
+        // Initialize pieces
+        board.init_pieces([
+            PieceType::Rook,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Queen,
+            PieceType::King,
+            PieceType::Bishop,
+            PieceType::Knight,
+            PieceType::Rook,
+        ]);
+        board.zobrist = board.compute_zobrist();
+        board.history.push(board.repetition_key());
+        board
+    }
+
+    /// Chess960 start position `number` (0-959) in Scharnagl's numbering,
+    /// in which 518 is the standard setup
+    pub fn chess960(number: usize) -> Board {
+        // Puts a piece on the nth still empty square of the back rank
+        fn place(back_rank: &mut [Option<PieceType>; 8], nth: usize, piece_type: PieceType) {
+            if let Some(square) = back_rank.iter_mut().filter(|square| square.is_none()).nth(nth) {
+                *square = Some(piece_type);
+            }
+        }
+
+        // Both knights on the remaining five squares, for each value of what is left of the number
+        const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+        let mut n = number % 960;
+        let mut back_rank = [None; 8];
+        back_rank[2 * (n % 4) + 1] = Some(PieceType::Bishop);
+        n /= 4;
+        back_rank[2 * (n % 4)] = Some(PieceType::Bishop);
+        n /= 4;
+        place(&mut back_rank, n % 6, PieceType::Queen);
+        n /= 6;
+
+        // Placing the second knight first keeps the index of the first one valid
+        let (first, second) = KNIGHTS[n];
+        place(&mut back_rank, second, PieceType::Knight);
+        place(&mut back_rank, first, PieceType::Knight);
+
+        // The king goes between the rooks on the last three squares
+        place(&mut back_rank, 0, PieceType::Rook);
+        place(&mut back_rank, 0, PieceType::King);
+        place(&mut back_rank, 0, PieceType::Rook);
+
+        let back_rank = back_rank.map(|piece_type| piece_type.expect("every square is filled"));
+        let mut rook_files = (0..8).filter(|&file| back_rank[file] == PieceType::Rook);
+        let queenside = rook_files.next();
+        let kingside = rook_files.next();
+
+        let mut board = Board::empty();
+        board.castling_rights = CastlingRights {
+            white_kingside: kingside,
+            white_queenside: queenside,
+            black_kingside: kingside,
+            black_queenside: queenside,
+        };
+        board.init_pieces(back_rank);
+        board.zobrist = board.compute_zobrist();
+        board.history.push(board.repetition_key());
+        board
+    }
+
+    pub(crate) fn empty() -> Board {
+        Board {
+            pieces: [0; 6],
+            colors: [0; 2],
+            castling_rights: CastlingRights::none(),
+            en_passant: None,
+            side_to_move: PieceColor::White,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist: 0,
+            history: Vec::new(),
+        }
+    }
+
+    fn init_pieces(&mut self, back_rank: [PieceType; 8]) {
+        for (file, &piece_type) in back_rank.iter().enumerate() {
+            // Set up white pieces
+            self.set_piece(Square::new(file, 0), Some(Piece { piece_type, color: PieceColor::White }));
+            self.set_piece(Square::new(file, 1), Some(Piece { piece_type: PieceType::Pawn, color: PieceColor::White }));
+
+            // Set up black pieces
+            self.set_piece(Square::new(file, 7), Some(Piece { piece_type, color: PieceColor::Black }));
+            self.set_piece(Square::new(file, 6), Some(Piece { piece_type: PieceType::Pawn, color: PieceColor::Black }));
+        }
+    }
+
+    /// Squares occupied by pieces of either color
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    /// Squares holding pieces of the given type and color
+    pub fn pieces_of(&self, piece_type: PieceType, color: PieceColor) -> Bitboard {
+        self.pieces[piece_type as usize] & self.colors[color as usize]
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let bit = square.bit();
+        let color = if self.colors[PieceColor::White as usize] & bit != 0 {
+            PieceColor::White
+        } else if self.colors[PieceColor::Black as usize] & bit != 0 {
+            PieceColor::Black
+        } else {
+            return None;
+        };
+
+        let piece_type = PieceType::ALL.into_iter().find(|&pt| self.pieces[pt as usize] & bit != 0)?;
+        Some(Piece { piece_type, color })
+    }
+
+    pub(crate) fn set_piece(&mut self, square: Square, piece: Option<Piece>) {
+        if let Some(old) = self.piece_at(square) {
+            self.zobrist ^= zobrist_piece(old, square);
+        }
+
+        let bit = square.bit();
+        for set in self.pieces.iter_mut().chain(self.colors.iter_mut()) {
+            *set &= !bit;
+        }
+
+        if let Some(piece) = piece {
+            self.pieces[piece.piece_type as usize] |= bit;
+            self.colors[piece.color as usize] |= bit;
+            self.zobrist ^= zobrist_piece(piece, square);
+        }
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.side_to_move
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling_rights
+    }
+
+    /// Square skipped over by a double pawn push on the previous move
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    /// Half-moves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    /// Polyglot-compatible Zobrist key of the position
+    pub fn zobrist_key(&self) -> u64 {
+        self.zobrist
+    }
+
+    /// Where the king of `color` stands
+    pub fn king_square(&self, color: PieceColor) -> Option<Square> {
+        bitboard_squares(self.pieces_of(PieceType::King, color)).next()
+    }
+
+    // File of the rook furthest from the king on the given side of it, on the back rank
+    pub(crate) fn outermost_rook_file(&self, color: PieceColor, kingside: bool) -> Option<usize> {
+        let back_rank = if color == PieceColor::White { 0 } else { 7 };
+        let king = self.king_square(color).filter(|king| king.rank() == back_rank)?;
+        let files = bitboard_squares(self.pieces_of(PieceType::Rook, color))
+            .filter(|rook| rook.rank() == back_rank && (rook.file() > king.file()) == kingside)
+            .map(|rook| rook.file());
+        if kingside {
+            files.max()
+        } else {
+            files.min()
+        }
+    }
+
+    /// Whether the side to move is checkmated
+    pub fn is_checkmate(&self) -> bool {
+        self.is_king_in_check(self.side_to_move) && self.legal_moves().is_empty()
+    }
+
+    /// Whether the side to move has no legal move but is not in check
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_king_in_check(self.side_to_move) && self.legal_moves().is_empty()
+    }
+
+    // For the repetition rules the en passant square only matters if the
+    // capture can actually be played, which is stricter than Polyglot's rule
+    pub(crate) fn repetition_key(&self) -> u64 {
+        let en_passant_key = self.en_passant_key();
+        let capturable = self.en_passant.is_some_and(|square| {
+            let them = self.side_to_move.opposite();
+            let capturers = PAWN_ATTACKS[them as usize][square.index()] & self.pieces_of(PieceType::Pawn, self.side_to_move);
+            bitboard_squares(capturers).any(|pawn| {
+                self.is_valid_move(Move { from: pawn, to: square, promotion: None, kind: MoveKind::EnPassant })
+            })
+        });
+
+        if capturable {
+            self.zobrist_key()
+        } else {
+            self.zobrist_key() ^ en_passant_key
+        }
+    }
+
+    /// How many times the current position has occurred, counting this one
+    pub fn repetition_count(&self) -> usize {
+        match self.history.last() {
+            Some(current) => self.history.iter().filter(|&key| key == current).count(),
+            None => 0,
+        }
+    }
+
+    /// Neither side can possibly checkmate: bare kings, a single minor piece,
+    /// or only bishops that all stand on squares of the same color
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy = self.pieces[PieceType::Pawn as usize]
+            | self.pieces[PieceType::Rook as usize]
+            | self.pieces[PieceType::Queen as usize];
+        if heavy != 0 {
+            return false;
+        }
+
+        let knights = self.pieces[PieceType::Knight as usize];
+        let bishops = self.pieces[PieceType::Bishop as usize];
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        // Two or more minor pieces only fail to mate if they are all same-colored bishops
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+    }
+
+    /// Draws that end the game without either player claiming them
+    pub fn automatic_draw(&self) -> Option<DrawReason> {
+        if self.is_insufficient_material() {
+            Some(DrawReason::InsufficientMaterial)
+        } else if self.repetition_count() >= 5 {
+            Some(DrawReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(DrawReason::SeventyFiveMoveRule)
+        } else {
+            None
+        }
+    }
+
+    /// Draws the player to move may claim instead of moving
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.repetition_count() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    // Moving the king loses both castling rights of its color, moving a
+    // castling rook or capturing it loses the right it belongs to
+    fn update_castling_rights(&mut self, square: Square) {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let king_moves = self.king_square(color) == Some(square);
+            for kingside in [true, false] {
+                if king_moves || self.castling_rook(color, kingside) == Some(square) {
+                    *self.castling_rights.rook_file_mut(color, kingside) = None;
+                }
+            }
+        }
+    }
+
+    /// Whether moving the piece on `from` to `to` is a pawn reaching the last rank
+    pub fn is_promotion(&self, from: Square, to: Square) -> bool {
+        match self.piece_at(from) {
+            Some(piece) if piece.piece_type == PieceType::Pawn => {
+                (piece.color == PieceColor::White && to.rank() == 7) ||
+                (piece.color == PieceColor::Black && to.rank() == 0)
+            }
+            _ => false,
+        }
+    }
+
+    // A promotion entered without a piece is played as a queen
+    pub(crate) fn with_default_promotion(&self, mv: Move) -> Move {
+        match mv.promotion {
+            None if self.is_promotion(mv.from, mv.to) => Move { promotion: Some(PieceType::Queen), ..mv },
+            _ => mv,
+        }
+    }
+
+    // The move as legal_moves gives it, whatever kind it was built with:
+    // the kind comes from the squares, as for create_move, and a promotion
+    // without a piece is a queen's
+    pub(crate) fn normalize_move(&self, mv: Move) -> Move {
+        let created = self.create_move(mv.from, mv.to, mv.promotion);
+        // A promotion given with castling is kept so the move stays illegal
+        self.with_default_promotion(Move { promotion: mv.promotion, ..created })
+    }
+
+    /// Describes moving whatever stands on `from` to `to` in this position;
+    /// the result still has to pass is_valid_move
+    pub fn create_move(&self, from: Square, to: Square, promotion: Option<PieceType>) -> Move {
+        let is_pawn = self.pieces[PieceType::Pawn as usize] & from.bit() != 0;
+        let is_king = self.pieces[PieceType::King as usize] & from.bit() != 0;
+
+        // Castling can be entered as the king taking its own rook, or as
+        // the king's move of two or more squares to the g- or c-file
+        if let Some(color) = self.piece_at(from).filter(|_| is_king).map(|king| king.color) {
+            for kingside in [true, false] {
+                if let Some(rook) = self.castling_rook(color, kingside) {
+                    let castle = Move { from, to: rook, promotion: None, kind: MoveKind::Castle };
+                    let king_to = castle.castling_destinations().0;
+                    if to == rook || (to == king_to && from.file().abs_diff(to.file()) >= 2) {
+                        return castle;
+                    }
+                }
+            }
+        }
+
+        let kind = if is_pawn && from.rank().abs_diff(to.rank()) == 2 {
+            MoveKind::DoublePawnPush
+        } else if self.occupied() & to.bit() != 0 {
+            MoveKind::Capture
+        } else if is_pawn && from.file() != to.file() {
+            MoveKind::EnPassant
+        } else {
+            MoveKind::Quiet
+        };
+
+        Move { from, to, promotion, kind }
+    }
+
+    /// A promoting move without an explicit piece promotes to a queen, and
+    /// the move's kind is worked out from its squares rather than trusted.
+    /// Returns what unmake_move needs to take the move back, or why the
+    /// move is illegal
+    pub fn make_move(&mut self, mv: Move) -> Result<Undo, MoveError> {
+        self.check_move(mv)?;
+        Ok(self.play_move(self.normalize_move(mv)))
+    }
+
+    // Plays a move known to be legal, such as one from legal_moves, without
+    // checking it again
+    pub(crate) fn play_move(&mut self, mv: Move) -> Undo {
+        let captured = match mv.kind {
+            MoveKind::EnPassant => self.piece_at(Square::new(mv.to.file(), mv.from.rank())),
+            MoveKind::Castle => None,
+            _ => self.piece_at(mv.to),
+        };
+
+        let undo = Undo {
+            mv,
+            captured,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            zobrist: self.zobrist,
+        };
+
+        // Take out the old castling and en passant keys; the new ones go in once the move is made
+        self.zobrist ^= self.castling_key() ^ self.en_passant_key();
+
+        // Captures and pawn moves reset the fifty-move counter
+        let is_pawn_move = self.pieces[PieceType::Pawn as usize] & mv.from.bit() != 0;
+        if is_pawn_move || mv.is_capture() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        // A double pawn push makes the skipped square available for en passant
+        self.en_passant = match mv.kind {
+            MoveKind::DoublePawnPush => Some(Square::new(mv.from.file(), (mv.from.rank() + mv.to.rank()) / 2)),
+            _ => None,
+        };
+
+        self.update_castling_rights(mv.from);
+        self.update_castling_rights(mv.to);
+        self.move_piece(mv);
+        if let Some(piece_type) = mv.promotion {
+            self.check_pawn_promotion(mv.to, piece_type);
+        }
+        if self.side_to_move == PieceColor::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = self.side_to_move.opposite();
+        self.zobrist ^= POLYGLOT_RANDOM[ZOBRIST_WHITE_TO_MOVE] ^ self.castling_key() ^ self.en_passant_key();
+        self.history.push(self.repetition_key());
+        undo
+    }
+
+    /// Takes back the move `undo` was returned for; moves must be taken back in reverse order
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let mv = undo.mv;
+        self.history.pop();
+        self.side_to_move = self.side_to_move.opposite();
+
+        if mv.kind == MoveKind::Castle {
+            let (king_to, rook_to) = mv.castling_destinations();
+            let (king, rook) = (self.piece_at(king_to), self.piece_at(rook_to));
+            self.set_piece(king_to, None);
+            self.set_piece(rook_to, None);
+            self.set_piece(mv.from, king);
+            self.set_piece(mv.to, rook);
+        } else {
+            // A promoted piece goes back as the pawn it was
+            let piece = match mv.promotion {
+                Some(_) => Some(Piece { piece_type: PieceType::Pawn, color: self.side_to_move }),
+                None => self.piece_at(mv.to),
+            };
+            self.set_piece(mv.from, piece);
+
+            match mv.kind {
+                MoveKind::EnPassant => {
+                    self.set_piece(mv.to, None);
+                    self.set_piece(Square::new(mv.to.file(), mv.from.rank()), undo.captured);
+                }
+                _ => self.set_piece(mv.to, undo.captured),
+            }
+        }
+
+        self.castling_rights = undo.castling_rights;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.zobrist = undo.zobrist;
+    }
+
+    fn move_piece(&mut self, mv: Move) {
+        let piece = self.piece_at(mv.from);
+
+        match mv.kind {
+            // Both pieces leave before either lands, as in Chess960 the king
+            // or rook may end up where the other one stood
+            MoveKind::Castle => {
+                let rook = self.piece_at(mv.to);
+                let (king_to, rook_to) = mv.castling_destinations();
+                self.set_piece(mv.from, None);
+                self.set_piece(mv.to, None);
+                self.set_piece(king_to, piece);
+                self.set_piece(rook_to, rook);
+                return;
+            }
+            // The captured pawn stands beside the destination square
+            MoveKind::EnPassant => self.set_piece(Square::new(mv.to.file(), mv.from.rank()), None),
+            _ => {}
+        }
+
+        self.set_piece(mv.to, piece);
+        self.set_piece(mv.from, None);
+    }
+
+    fn check_pawn_promotion(&mut self, to: Square, promote_to: PieceType) {
+        if let Some(piece) = self.piece_at(to) {
+            if piece.piece_type == PieceType::Pawn {
+                // Check if pawn reached the opposite end
+                if (piece.color == PieceColor::White && to.rank() == 7) ||
+                   (piece.color == PieceColor::Black && to.rank() == 0) {
+                    self.set_piece(to, Some(Piece {
+                        piece_type: promote_to,
+                        color: piece.color,
+                    }));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Extended Position Description: a position plus operations such as the
+//! best move, as used by test suites like WAC, ECM and STS.
+
+use crate::board::{Board, Move, MoveError};
+use crate::notation::FenError;
+use std::fmt;
+
+/// Why an EPD record could not be read
+#[derive(Debug, PartialEq)]
+pub enum EpdError {
+    InvalidFen(FenError),
+    /// An operation without its closing ';'
+    UnterminatedOperation(String),
+    UnterminatedString,
+    /// An opcode not starting with a letter, as when a full FEN with its
+    /// move counters is given in place of the four EPD fields
+    InvalidOpcode(String),
+    InvalidOperand { opcode: String, operand: String },
+    IllegalMove { opcode: String, san: String, error: MoveError },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::InvalidFen(err) => write!(f, "invalid position: {}", err),
+            EpdError::UnterminatedOperation(op) => write!(f, "operation '{}' must end with ';'", op),
+            EpdError::UnterminatedString => write!(f, "a quoted operand is never closed"),
+            EpdError::InvalidOpcode(opcode) => write!(f, "'{}' is not an opcode, which must start with a letter", opcode),
+            EpdError::InvalidOperand { opcode, operand } => write!(f, "'{}' is not a valid operand for {}", operand, opcode),
+            EpdError::IllegalMove { opcode, san, error } => write!(f, "{} move {} can't be played: {}", opcode, san, error),
+        }
+    }
+}
+
+/// A position with its EPD operations, in the order they were written
+#[derive(Clone)]
+pub struct Epd {
+    pub board: Board,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+// Operations whose moves are checked against the position when read
+const MOVE_OPCODES: [&str; 4] = ["bm", "am", "pm", "sm"];
+
+// Operations that count moves and must hold a number
+const NUMBER_OPCODES: [&str; 5] = ["dm", "acd", "acn", "hmvc", "fmvn"];
+
+impl Epd {
+    /// A position without operations
+    pub fn new(board: Board) -> Epd {
+        Epd { board, operations: Vec::new() }
+    }
+
+    /// Reads one EPD line: the first four FEN fields, then operations such as
+    /// `bm Qg6; id "WAC.001";`. The hmvc and fmvn operations set the move counters
+    pub fn parse(line: &str) -> Result<Epd, EpdError> {
+        let mut rest = line.trim();
+        let mut position = Vec::new();
+        while position.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            position.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if position.len() < 4 {
+            return Err(EpdError::InvalidFen(FenError::WrongFieldCount(position.len())));
+        }
+
+        let operations = parse_operations(rest)?;
+        for (opcode, operands) in &operations {
+            if NUMBER_OPCODES.contains(&opcode.as_str()) {
+                match operands.as_slice() {
+                    [number] if number.parse::<u32>().is_ok() => {}
+                    _ => {
+                        let operand = operands.join(" ");
+                        return Err(EpdError::InvalidOperand { opcode: opcode.clone(), operand });
+                    }
+                }
+            }
+        }
+
+        let counter = |opcode: &str, default: &'static str| {
+            operations.iter().find(|(op, _)| op == opcode).map_or(default.to_string(), |(_, operands)| operands[0].clone())
+        };
+        let fen = format!("{} {} {}", position.join(" "), counter("hmvc", "0"), counter("fmvn", "1"));
+        let board = Board::from_fen(&fen).map_err(EpdError::InvalidFen)?;
+
+        // Moves are checked now so the accessors can rely on them
+        for (opcode, operands) in &operations {
+            if MOVE_OPCODES.contains(&opcode.as_str()) {
+                for san in operands {
+                    board.parse_san(san).map_err(|error| EpdError::IllegalMove {
+                        opcode: opcode.clone(),
+                        san: san.clone(),
+                        error,
+                    })?;
+                }
+            }
+        }
+
+        Ok(Epd { board, operations })
+    }
+
+    /// The operands of an operation, if the record has it
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    /// Adds an operation, replacing one with the same opcode
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(op, _)| op == opcode) {
+            Some((_, existing)) => *existing = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    // A promotion written without its piece is a queen promotion, as when played
+    fn moves(&self, opcode: &str) -> Vec<Move> {
+        self.operation(opcode)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|san| self.board.parse_san(san).ok())
+            .map(|mv| self.board.with_default_promotion(mv))
+            .collect()
+    }
+
+    /// The best moves ("bm"); any of them solves the position
+    pub fn best_moves(&self) -> Vec<Move> {
+        self.moves("bm")
+    }
+
+    /// The moves to avoid ("am")
+    pub fn avoid_moves(&self) -> Vec<Move> {
+        self.moves("am")
+    }
+
+    /// The record's name ("id")
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(String::as_str)
+    }
+
+    /// The first comment ("c0")
+    pub fn comment(&self) -> Option<&str> {
+        self.operation("c0").and_then(|operands| operands.first()).map(String::as_str)
+    }
+
+    /// The number of moves to a forced mate ("dm")
+    pub fn mate_in(&self) -> Option<u32> {
+        self.operation("dm").and_then(|operands| operands.first()).and_then(|n| n.parse().ok())
+    }
+
+    /// The record as one EPD line
+    pub fn to_epd(&self) -> String {
+        let fen = self.board.to_fen();
+        let mut epd: String = fen.split(' ').take(4).collect::<Vec<_>>().join(" ");
+        for (opcode, operands) in &self.operations {
+            epd.push(' ');
+            epd.push_str(opcode);
+            for operand in operands {
+                epd.push(' ');
+                // Text operations are always quoted, anything else only when it has to be
+                let is_text = opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c'));
+                if is_text || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"') {
+                    epd.push('"');
+                    epd.push_str(&operand.replace('\\', "\\\\").replace('"', "\\\""));
+                    epd.push('"');
+                } else {
+                    epd.push_str(operand);
+                }
+            }
+            epd.push(';');
+        }
+        epd
+    }
+}
+
+// Reads `opcode operand ...;` operations, where a quoted operand may hold
+// spaces and semicolons
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            ';' => {
+                if words.is_empty() {
+                    continue;
+                }
+                let opcode = words.remove(0);
+                let valid = opcode.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && opcode.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(EpdError::InvalidOpcode(opcode));
+                }
+                operations.push((opcode, std::mem::take(&mut words)));
+            }
+            '"' => {
+                let mut operand = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => operand.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => operand.push(c),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                words.push(operand);
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';' && *c != '"') {
+                    word.push(c);
+                }
+                words.push(word);
+            }
+        }
+    }
+
+    if !words.is_empty() {
+        return Err(EpdError::UnterminatedOperation(words.join(" ")));
+    }
+    Ok(operations)
+}
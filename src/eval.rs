@@ -0,0 +1,160 @@
+//! Static evaluation of positions.
+
+use crate::board::{Board, PieceColor, PieceType, Square};
+
+/// Scores the position from the point of view of `color`, in centipawns:
+/// material, piece-square tables and a bonus for every legal move
+pub fn evaluate(board: &Board, color: PieceColor) -> i32 {
+    let mut score = 0;
+
+    // Piece position tables for improved evaluation
+    let pawn_position = [
+        0,  0,  0,  0,  0,  0,  0,  0,
+        50, 50, 50, 50, 50, 50, 50, 50,
+        10, 10, 20, 30, 30, 20, 10, 10,
+        5,  5, 10, 25, 25, 10,  5,  5,
+        0,  0,  0, 20, 20,  0,  0,  0,
+        5, -5,-10,  0,  0,-10, -5,  5,
+        5, 10, 10,-20,-20, 10, 10,  5,
+        0,  0,  0,  0,  0,  0,  0,  0
+    ];
+
+    let knight_position = [
+        -50,-40,-30,-30,-30,-30,-40,-50,
+        -40,-20,  0,  0,  0,  0,-20,-40,
+        -30,  0, 10, 15, 15, 10,  0,-30,
+        -30,  5, 15, 20, 20, 15,  5,-30,
+        -30,  0, 15, 20, 20, 15,  0,-30,
+        -30,  5, 10, 15, 15, 10,  5,-30,
+        -40,-20,  0,  5,  5,  0,-20,-40,
+        -50,-40,-30,-30,-30,-30,-40,-50
+    ];
+
+    let bishop_position = [
+        -20,-10,-10,-10,-10,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5, 10, 10,  5,  0,-10,
+        -10,  5,  5, 10, 10,  5,  5,-10,
+        -10,  0, 10, 10, 10, 10,  0,-10,
+        -10, 10, 10, 10, 10, 10, 10,-10,
+        -10,  5,  0,  0,  0,  0,  5,-10,
+        -20,-10,-10,-10,-10,-10,-10,-20
+    ];
+
+    let rook_position = [
+        0,  0,  0,  0,  0,  0,  0,  0,
+        5, 10, 10, 10, 10, 10, 10,  5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        -5,  0,  0,  0,  0,  0,  0, -5,
+        0,  0,  0,  5,  5,  0,  0,  0
+    ];
+
+    let queen_position = [
+        -20,-10,-10, -5, -5,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5,  5,  5,  5,  0,-10,
+        -5,  0,  5,  5,  5,  5,  0, -5,
+        0,  0,  5,  5,  5,  5,  0, -5,
+        -10,  5,  5,  5,  5,  5,  0,-10,
+        -10,  0,  5,  0,  0,  0,  0,-10,
+        -20,-10,-10, -5, -5,-10,-10,-20
+    ];
+
+    let king_position = [
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -20,-30,-30,-40,-40,-30,-30,-20,
+        -10,-20,-20,-20,-20,-20,-20,-10,
+        20, 20,  0,  0,  0,  0, 20, 20,
+        20, 30, 10,  0,  0, 10, 30, 20
+    ];
+
+    for i in 0..8 {
+        for j in 0..8 {
+            if let Some(piece) = board.piece_at(Square::new(j, 7 - i)) {
+                let position_value = match piece.piece_type {
+                    PieceType::Pawn => {
+                        let idx = if piece.color == color {
+                            i * 8 + j
+                        } else {
+                            (7 - i) * 8 + j
+                        };
+                        pawn_position[idx]
+                    },
+                    PieceType::Knight => {
+                        let idx = if piece.color == color {
+                            i * 8 + j
+                        } else {
+                            (7 - i) * 8 + j
+                        };
+                        knight_position[idx]
+                    },
+                    PieceType::Bishop => {
+                        let idx = if piece.color == color {
+                            i * 8 + j
+                        } else {
+                            (7 - i) * 8 + j
+                        };
+                        bishop_position[idx]
+                    },
+                    PieceType::Rook => {
+                        let idx = if piece.color == color {
+                            i * 8 + j
+                        } else {
+                            (7 - i) * 8 + j
+                        };
+                        rook_position[idx]
+                    },
+                    PieceType::Queen => {
+                        let idx = if piece.color == color {
+                            i * 8 + j
+                        } else {
+                            (7 - i) * 8 + j
+                        };
+                        queen_position[idx]
+                    },
+                    PieceType::King => {
+                        let idx = if piece.color == color {
+                            i * 8 + j
+                        } else {
+                            (7 - i) * 8 + j
+                        };
+                        king_position[idx]
+                    },
+                };
+
+                let piece_value = match piece.piece_type {
+                    PieceType::Pawn => 100,
+                    PieceType::Knight => 320,
+                    PieceType::Bishop => 330,
+                    PieceType::Rook => 500,
+                    PieceType::Queen => 900,
+                    PieceType::King => 20000,
+                };
+
+                // Add mobility bonus
+                let mobility_bonus = if piece.color == color {
+                    count_legal_moves(board, Square::new(j, 7 - i)) as i32 * 10
+                } else {
+                    -(count_legal_moves(board, Square::new(j, 7 - i)) as i32 * 10)
+                };
+
+                if piece.color == color {
+                    score += piece_value + position_value + mobility_bonus;
+                } else {
+                    score -= piece_value + position_value - mobility_bonus;
+                }
+            }
+        }
+    }
+    score
+}
+
+fn count_legal_moves(board: &Board, from: Square) -> usize {
+    board.legal_moves_from(from).len()
+}
@@ -0,0 +1,256 @@
+//! A game in progress: the moves played, the clocks and how it ended.
+
+use crate::board::{Board, DrawReason, Move, MoveError, PieceColor, Undo};
+use std::time::{Duration, Instant};
+
+/// The outcome of a finished game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    /// The result for a win by `color`
+    pub fn win(color: PieceColor) -> GameResult {
+        match color {
+            PieceColor::White => GameResult::WhiteWins,
+            PieceColor::Black => GameResult::BlackWins,
+        }
+    }
+
+    /// The result as written in PGN: "1-0", "0-1" or "1/2-1/2"
+    pub fn to_pgn(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// How a game came to an end
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    Timeout,
+    Agreement,
+    Repetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+impl Termination {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::Resignation => "resignation",
+            Termination::Timeout => "timeout",
+            Termination::Agreement => "agreement",
+            Termination::Repetition => "repetition",
+            Termination::FiftyMoveRule => "fifty-move rule",
+            Termination::InsufficientMaterial => "insufficient material",
+        }
+    }
+}
+
+impl From<DrawReason> for Termination {
+    fn from(reason: DrawReason) -> Termination {
+        match reason {
+            DrawReason::ThreefoldRepetition | DrawReason::FivefoldRepetition => Termination::Repetition,
+            DrawReason::FiftyMoveRule | DrawReason::SeventyFiveMoveRule => Termination::FiftyMoveRule,
+            DrawReason::InsufficientMaterial => Termination::InsufficientMaterial,
+        }
+    }
+}
+
+/// Time for the whole game per player, plus an increment added after each move
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeControl {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+/// A game from its start position, keeping every move so it can be
+/// taken back, replayed or exported
+#[derive(Clone)]
+pub struct Game {
+    start: Board,
+    board: Board,
+    moves: Vec<Move>,
+    undos: Vec<Undo>,
+    time_control: Option<TimeControl>,
+    // Time left for White and Black, counted while it is their turn
+    clocks: [Duration; 2],
+    turn_started: Instant,
+    outcome: Option<(GameResult, Termination)>,
+}
+
+impl Game {
+    /// A game starting from `board`, without a time limit
+    pub fn new(board: Board) -> Game {
+        let mut game = Game {
+            start: board.clone(),
+            board,
+            moves: Vec::new(),
+            undos: Vec::new(),
+            time_control: None,
+            clocks: [Duration::ZERO; 2],
+            turn_started: Instant::now(),
+            outcome: None,
+        };
+        game.update_outcome();
+        game
+    }
+
+    /// A game starting from `board` in which both players' clocks run
+    pub fn with_time_control(board: Board, time_control: TimeControl) -> Game {
+        let mut game = Game::new(board);
+        game.time_control = Some(time_control);
+        game.clocks = [time_control.initial; 2];
+        game
+    }
+
+    /// The current position
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The position the game started from
+    pub fn start_position(&self) -> &Board {
+        &self.start
+    }
+
+    /// The moves played so far, in order
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// The moves played so far in SAN
+    pub fn san_moves(&self) -> Vec<String> {
+        let mut board = self.start.clone();
+        let mut sans = Vec::with_capacity(self.moves.len());
+        for &mv in &self.moves {
+            sans.push(mv.to_san(&board));
+            board.play_move(mv);
+        }
+        sans
+    }
+
+    /// Every position of the game, from the start position to the current one
+    pub fn positions(&self) -> Vec<Board> {
+        let mut board = self.start.clone();
+        let mut positions = vec![board.clone()];
+        for &mv in &self.moves {
+            board.play_move(mv);
+            positions.push(board.clone());
+        }
+        positions
+    }
+
+    pub fn time_control(&self) -> Option<TimeControl> {
+        self.time_control
+    }
+
+    /// Time left on the clock of `color`, including the running turn
+    pub fn remaining_time(&self, color: PieceColor) -> Option<Duration> {
+        self.time_control?;
+        let mut remaining = self.clocks[color as usize];
+        if color == self.board.side_to_move() && self.outcome.is_none() {
+            remaining = remaining.saturating_sub(self.turn_started.elapsed());
+        }
+        Some(remaining)
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.outcome.map(|(result, _)| result)
+    }
+
+    pub fn termination(&self) -> Option<Termination> {
+        self.outcome.map(|(_, termination)| termination)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// Plays a move for the side to move. Fails if the game is over, the
+    /// move is illegal, or the player ran out of time before making it
+    pub fn make_move(&mut self, mv: Move) -> Result<(), MoveError> {
+        if self.outcome.is_some() || self.check_time() {
+            return Err(MoveError::GameOver);
+        }
+
+        let undo = self.board.make_move(mv)?;
+
+        let mover = self.board.side_to_move().opposite();
+        if let Some(time_control) = self.time_control {
+            let clock = &mut self.clocks[mover as usize];
+            *clock = clock.saturating_sub(self.turn_started.elapsed()) + time_control.increment;
+        }
+        self.turn_started = Instant::now();
+
+        self.moves.push(undo.played_move());
+        self.undos.push(undo);
+        self.update_outcome();
+        Ok(())
+    }
+
+    /// Takes back the last move, which also undoes the end of the game
+    pub fn take_back(&mut self) -> Option<Move> {
+        let undo = self.undos.pop()?;
+        self.moves.pop();
+        self.board.unmake_move(undo);
+        self.outcome = None;
+        self.turn_started = Instant::now();
+        Some(undo.played_move())
+    }
+
+    /// Ends the game with a loss for the side to move if its time is up
+    pub fn check_time(&mut self) -> bool {
+        let color = self.board.side_to_move();
+        if self.outcome.is_none() && self.remaining_time(color) == Some(Duration::ZERO) {
+            self.clocks[color as usize] = Duration::ZERO;
+            self.outcome = Some((GameResult::win(color.opposite()), Termination::Timeout));
+        }
+        self.termination() == Some(Termination::Timeout)
+    }
+
+    pub fn resign(&mut self, color: PieceColor) {
+        if self.outcome.is_none() {
+            self.outcome = Some((GameResult::win(color.opposite()), Termination::Resignation));
+        }
+    }
+
+    pub fn agree_draw(&mut self) {
+        if self.outcome.is_none() {
+            self.outcome = Some((GameResult::Draw, Termination::Agreement));
+        }
+    }
+
+    /// Ends the game in a draw if the side to move may claim one
+    pub fn claim_draw(&mut self) -> Option<DrawReason> {
+        if self.outcome.is_some() {
+            return None;
+        }
+        let reason = self.board.claimable_draw()?;
+        self.outcome = Some((GameResult::Draw, reason.into()));
+        Some(reason)
+    }
+
+    // Checkmate, stalemate and the draws nobody has to claim end the game by themselves
+    fn update_outcome(&mut self) {
+        let color = self.board.side_to_move();
+        self.outcome = if self.board.is_checkmate() {
+            Some((GameResult::win(color.opposite()), Termination::Checkmate))
+        } else if self.board.is_stalemate() {
+            Some((GameResult::Draw, Termination::Stalemate))
+        } else {
+            self.board.automatic_draw().map(|reason| (GameResult::Draw, reason.into()))
+        };
+    }
+}
@@ -0,0 +1,27 @@
+//! Chess rules, notation and a small alpha-beta engine, as used by the
+//! `rustchess` terminal game.
+//!
+//! A [`Board`] holds a position and plays [`Move`]s on it; [`ChessAI`]
+//! searches it for a move. A [`Game`] adds the moves played, the clocks
+//! and the result. Positions can be read and written as FEN and EPD, and games
+//! saved and loaded as PGN. The [`uci`] and [`xboard`] modules let chess GUIs
+//! play against the AI.
+
+pub mod board;
+pub mod epd;
+pub mod eval;
+pub mod game;
+pub mod movegen;
+pub mod notation;
+pub mod pgn;
+pub mod search;
+pub mod uci;
+pub mod xboard;
+mod zobrist;
+
+pub use board::{bitboard_squares, Bitboard, Board, CastlingRights, DrawReason, Move, MoveError, MoveKind, Piece, PieceColor, PieceType, Square, Undo};
+pub use epd::{Epd, EpdError};
+pub use game::{Game, GameResult, Termination, TimeControl};
+pub use notation::{parse_move_input, parse_promotion, piece_from_char, piece_to_char, promotion_name, FenError};
+pub use pgn::{parse_pgn, PgnError, PgnGame, PgnTags};
+pub use search::{ChessAI, SearchInfo, SearchLimits};
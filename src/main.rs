@@ -1,690 +1,348 @@
 use colored::*;
+use rand::Rng;
+use rustchess::{parse_pgn, parse_promotion, Board, ChessAI, Epd, Game, MoveError, PgnTags, PieceColor, PieceType, Square, Termination};
+use std::fs::{self, OpenOptions};
 use std::io::{self, Write};
+use std::time::Instant;
 use tokio::time::{sleep, Duration};
 
-#[derive(Clone, Copy, PartialEq)]
-enum PieceColor {
-    White,
-    Black,
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    }
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum PieceType {
-    King,
-    Queen,
-    Rook,
-    Bishop,
-    Knight,
-    Pawn,
-}
+// Prints the board with White at the bottom
+fn display(board: &Board) {
+    // A king in check is shown together with the pieces checking it
+    let checkers = board.checkers();
+    let highlighted = match board.king_square(board.side_to_move()) {
+        Some(king) if checkers != 0 => checkers | king.bit(),
+        _ => 0,
+    };
 
-#[derive(Clone, Copy)]
-struct Piece {
-    piece_type: PieceType,
-    color: PieceColor,
+    println!("  a b c d e f g h");
+    println!("  ─────────────");
+    for rank in (0..8).rev() {
+        print!("{} ", rank + 1);
+        for file in 0..8 {
+            let square = Square::new(file, rank);
+            let piece_str = match board.piece_at(square) {
+                Some(piece) => {
+                    let symbol = match (piece.piece_type, piece.color) {
+                        (PieceType::King, PieceColor::White) => "♔",
+                        (PieceType::Queen, PieceColor::White) => "♕",
+                        (PieceType::Rook, PieceColor::White) => "♖",
+                        (PieceType::Bishop, PieceColor::White) => "♗",
+                        (PieceType::Knight, PieceColor::White) => "♘",
+                        (PieceType::Pawn, PieceColor::White) => "♙",
+                        (PieceType::King, PieceColor::Black) => "♚",
+                        (PieceType::Queen, PieceColor::Black) => "♛",
+                        (PieceType::Rook, PieceColor::Black) => "♜",
+                        (PieceType::Bishop, PieceColor::Black) => "♝",
+                        (PieceType::Knight, PieceColor::Black) => "♞",
+                        (PieceType::Pawn, PieceColor::Black) => "♟",
+                    };
+                    let symbol = if piece.color == PieceColor::White {
+                        symbol.white()
+                    } else {
+                        symbol.black()
+                    };
+                    if highlighted & square.bit() != 0 {
+                        symbol.on_red().to_string()
+                    } else {
+                        symbol.to_string()
+                    }
+                }
+                None => "·".to_string(),
+            };
+            print!("{} ", piece_str);
+        }
+        println!("{}", rank + 1);
+    }
+    println!("  ─────────────");
+    println!("  a b c d e f g h");
 }
 
-#[derive(Clone)]
-struct Board {
-    squares: [[Option<Piece>; 8]; 8],
-}
+// Where finished and abandoned games are appended
+const GAMES_FILE: &str = "games.pgn";
 
-impl Board {
-    fn new() -> Board {
-        let mut board = Board {
-            squares: [[None; 8]; 8],
-        };
-        
-        // Initialize pieces
-        board.init_pieces();
-        board
-    }
-
-    fn init_pieces(&mut self) {
-        // Set up white pieces
-        self.squares[7][0] = Some(Piece { piece_type: PieceType::Rook, color: PieceColor::White });
-        self.squares[7][1] = Some(Piece { piece_type: PieceType::Knight, color: PieceColor::White });
-        self.squares[7][2] = Some(Piece { piece_type: PieceType::Bishop, color: PieceColor::White });
-        self.squares[7][3] = Some(Piece { piece_type: PieceType::Queen, color: PieceColor::White });
-        self.squares[7][4] = Some(Piece { piece_type: PieceType::King, color: PieceColor::White });
-        self.squares[7][5] = Some(Piece { piece_type: PieceType::Bishop, color: PieceColor::White });
-        self.squares[7][6] = Some(Piece { piece_type: PieceType::Knight, color: PieceColor::White });
-        self.squares[7][7] = Some(Piece { piece_type: PieceType::Rook, color: PieceColor::White });
-
-        // Set up black pieces
-        self.squares[0][0] = Some(Piece { piece_type: PieceType::Rook, color: PieceColor::Black });
-        self.squares[0][1] = Some(Piece { piece_type: PieceType::Knight, color: PieceColor::Black });
-        self.squares[0][2] = Some(Piece { piece_type: PieceType::Bishop, color: PieceColor::Black });
-        self.squares[0][3] = Some(Piece { piece_type: PieceType::Queen, color: PieceColor::Black });
-        self.squares[0][4] = Some(Piece { piece_type: PieceType::King, color: PieceColor::Black });
-        self.squares[0][5] = Some(Piece { piece_type: PieceType::Bishop, color: PieceColor::Black });
-        self.squares[0][6] = Some(Piece { piece_type: PieceType::Knight, color: PieceColor::Black });
-        self.squares[0][7] = Some(Piece { piece_type: PieceType::Rook, color: PieceColor::Black });
-
-        // Set up pawns
-        for i in 0..8 {
-            self.squares[1][i] = Some(Piece { piece_type: PieceType::Pawn, color: PieceColor::Black });
-            self.squares[6][i] = Some(Piece { piece_type: PieceType::Pawn, color: PieceColor::White });
-        }
+// Appends the game to a PGN file, or creates it
+fn save_game(game: &Game, tags: &PgnTags, path: &str, append: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)?;
+    if append && file.metadata()?.len() > 0 {
+        writeln!(file)?;
     }
+    file.write_all(game.to_pgn(tags).as_bytes())
+}
 
-    fn display(&self) {
-        println!("  a b c d e f g h");
-        println!("  ─────────────");
-        for i in 0..8 {
-            print!("{} ", 8 - i);
-            for j in 0..8 {
-                let piece_str = match &self.squares[i][j] {
-                    Some(piece) => {
-                        let symbol = match (piece.piece_type, piece.color) {
-                            (PieceType::King, PieceColor::White) => "♔",
-                            (PieceType::Queen, PieceColor::White) => "♕",
-                            (PieceType::Rook, PieceColor::White) => "♖",
-                            (PieceType::Bishop, PieceColor::White) => "♗",
-                            (PieceType::Knight, PieceColor::White) => "♘",
-                            (PieceType::Pawn, PieceColor::White) => "♙",
-                            (PieceType::King, PieceColor::Black) => "♚",
-                            (PieceType::Queen, PieceColor::Black) => "♛",
-                            (PieceType::Rook, PieceColor::Black) => "♜",
-                            (PieceType::Bishop, PieceColor::Black) => "♝",
-                            (PieceType::Knight, PieceColor::Black) => "♞",
-                            (PieceType::Pawn, PieceColor::Black) => "♟",
-                        };
-                        if piece.color == PieceColor::White {
-                            symbol.white().to_string()
-                        } else {
-                            symbol.black().to_string()
-                        }
-                    }
-                    None => "·".to_string(),
-                };
-                print!("{} ", piece_str);
-            }
-            println!("{}", 8 - i);
-        }
-        println!("  ─────────────");
-        println!("  a b c d e f g h");
-    }
+// None once the input has ended, which must not be taken for an empty line
+fn read_line(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap();
 
-    fn is_valid_position(x: i32, y: i32) -> bool {
-        x >= 0 && x < 8 && y >= 0 && y < 8
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
     }
+}
 
-    fn is_king_in_check(&self, color: PieceColor) -> bool {
-        // Find king position
-        let mut king_pos = None;
-        for i in 0..8 {
-            for j in 0..8 {
-                if let Some(piece) = self.squares[i][j] {
-                    if piece.piece_type == PieceType::King && piece.color == color {
-                        king_pos = Some((i, j));
-                        break;
-                    }
-                }
-            }
+// Steps through a game from a PGN file, forwards and backwards
+fn replay_pgn() {
+    let Some(path) = read_line("Enter PGN file: ") else {
+        return;
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) => {
+            println!("Could not read {}: {}", path, err);
+            return;
         }
-
-        if let Some((king_i, king_j)) = king_pos {
-            // Check if any opponent piece can capture the king
-            for i in 0..8 {
-                for j in 0..8 {
-                    if let Some(piece) = self.squares[i][j] {
-                        if piece.color != color {
-                            let from = format!("{}{}", (b'a' + j as u8) as char, 8 - i);
-                            let to = format!("{}{}", (b'a' + king_j as u8) as char, 8 - king_i);
-                            if self.is_valid_move(&from, &to) {
-                                return true;
-                            }
-                        }
-                    }
-                }
-            }
+    };
+    let games = match parse_pgn(&text) {
+        Ok(games) if games.is_empty() => {
+            println!("No games found in {}", path);
+            return;
         }
-        false
-    }
-
-    fn is_checkmate(&self, color: PieceColor) -> bool {
-        if !self.is_king_in_check(color) {
-            return false;
-        }
-
-        // Try all possible moves to see if any can get out of check
-        for i in 0..8 {
-            for j in 0..8 {
-                if let Some(piece) = self.squares[i][j] {
-                    if piece.color == color {
-                        let from = format!("{}{}", (b'a' + j as u8) as char, 8 - i);
-                        for to_i in 0..8 {
-                            for to_j in 0..8 {
-                                let to = format!("{}{}", (b'a' + to_j as u8) as char, 8 - to_i);
-                                if self.is_valid_move(&from, &to) {
-                                    let mut new_board = self.clone();
-                                    if new_board.make_move(&from, &to) {
-                                        if !new_board.is_king_in_check(color) {
-                                            return false;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        Ok(games) => games,
+        Err(err) => {
+            println!("Invalid PGN: {}", err);
+            return;
         }
-        true
-    }
+    };
 
-    fn is_stalemate(&self, color: PieceColor) -> bool {
-        if self.is_king_in_check(color) {
-            return false;
-        }
-
-        // Check if any legal move exists
-        for i in 0..8 {
-            for j in 0..8 {
-                if let Some(piece) = self.squares[i][j] {
-                    if piece.color == color {
-                        let from = format!("{}{}", (b'a' + j as u8) as char, 8 - i);
-                        for to_i in 0..8 {
-                            for to_j in 0..8 {
-                                let to = format!("{}{}", (b'a' + to_j as u8) as char, 8 - to_i);
-                                if self.is_valid_move(&from, &to) {
-                                    let mut new_board = self.clone();
-                                    if new_board.make_move(&from, &to) {
-                                        if !new_board.is_king_in_check(color) {
-                                            return false;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    let pgn_game = if games.len() == 1 {
+        &games[0]
+    } else {
+        for (i, game) in games.iter().enumerate() {
+            let result = game.result.map_or("*", |result| result.to_pgn());
+            let tag = |name| game.tag(name).unwrap_or("?");
+            println!("{}. {} - {}, {} ({})", i + 1, tag("White"), tag("Black"), tag("Event"), result);
+        }
+        loop {
+            let Some(choice) = read_line(&format!("Choose a game (1-{}): ", games.len())) else {
+                return;
+            };
+            if let Some(game) = choice.parse::<usize>().ok().and_then(|n| games.get(n.wrapping_sub(1))) {
+                break game;
             }
         }
-        true
-    }
-
-    fn is_valid_move(&self, from: &str, to: &str) -> bool {
-        let (from_x, from_y) = match parse_position(from) {
-            (Some(x), Some(y)) => (x, y),
-            _ => return false,
-        };
-        
-        let (to_x, to_y) = match parse_position(to) {
-            (Some(x), Some(y)) => (x, y),
-            _ => return false,
-        };
-
-        let piece = match self.squares[from_y][from_x] {
-            Some(p) => p,
-            None => return false,
-        };
+    };
 
-        // Check if destination has a piece of the same color
-        if let Some(dest_piece) = self.squares[to_y][to_x] {
-            if dest_piece.color == piece.color {
-                return false;
-            }
+    let start = pgn_game.game.start_position();
+    let positions = pgn_game.game.positions();
+    let sans = pgn_game.game.san_moves();
+    let first_ply = if start.side_to_move() == PieceColor::White { 0 } else { 1 };
+    let mut ply = 0;
+    loop {
+        display(&positions[ply]);
+        if ply == 0 {
+            println!("Start position");
+        } else {
+            // Counted from the start position's move number and side to move
+            let index = ply - 1 + first_ply;
+            let number = start.fullmove_number() as usize + index / 2;
+            let dots = if index % 2 == 0 { "." } else { "..." };
+            println!("{}{} {}", number, dots, sans[ply - 1]);
         }
-
-        let basic_valid = match piece.piece_type {
-            PieceType::Pawn => self.is_valid_pawn_move(from_x, from_y, to_x, to_y, piece.color),
-            PieceType::Rook => self.is_valid_rook_move(from_x, from_y, to_x, to_y),
-            PieceType::Knight => self.is_valid_knight_move(from_x, from_y, to_x, to_y),
-            PieceType::Bishop => self.is_valid_bishop_move(from_x, from_y, to_x, to_y),
-            PieceType::Queen => self.is_valid_queen_move(from_x, from_y, to_x, to_y),
-            PieceType::King => self.is_valid_king_move(from_x, from_y, to_x, to_y),
-        };
-
-        if !basic_valid {
-            return false;
+        if ply == sans.len() {
+            println!("End of game: {}", pgn_game.result.map_or("*", |result| result.to_pgn()));
         }
 
-        // Check if move puts or leaves own king in check
-        let mut new_board = self.clone();
-        new_board.squares[to_y][to_x] = new_board.squares[from_y][from_x];
-        new_board.squares[from_y][from_x] = None;
-        !new_board.is_king_in_check(piece.color)
-    }
-
-    fn is_valid_pawn_move(&self, from_x: usize, from_y: usize, to_x: usize, to_y: usize, color: PieceColor) -> bool {
-        let direction = if color == PieceColor::White { -1 } else { 1 };
-        let start_rank = if color == PieceColor::White { 6 } else { 1 };
-        
-        // Convert to signed integers for calculations
-        let dx = (to_x as i32) - (from_x as i32);
-        let dy = (to_y as i32) - (from_y as i32);
-
-        // Basic forward movement
-        if dx == 0 {
-            // Single square forward
-            if dy == direction {
-                return self.squares[to_y][to_x].is_none();
-            }
-            // Double square forward from starting position
-            if from_y == start_rank && dy == 2 * direction {
-                let intermediate_y = (from_y as i32 + direction) as usize;
-                return self.squares[intermediate_y][from_x].is_none() 
-                    && self.squares[to_y][to_x].is_none();
-            }
-        }
-        // Capture diagonally
-        else if dx.abs() == 1 && dy == direction {
-            return self.squares[to_y][to_x].is_some();
+        match read_line("[Enter] next, 'p' previous, 's' start, 'e' end, 'q' back to the menu: ").as_deref() {
+            Some("" | "n") => ply = (ply + 1).min(sans.len()),
+            Some("p") => ply = ply.saturating_sub(1),
+            Some("s") => ply = 0,
+            Some("e") => ply = sans.len(),
+            Some("q") | None => return,
+            Some(_) => {}
         }
-
-        false
     }
+}
 
-    fn is_valid_rook_move(&self, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-        if from_x != to_x && from_y != to_y {
-            return false;
-        }
-
-        let (start, end, is_vertical) = if from_x == to_x {
-            (from_y.min(to_y) + 1, from_y.max(to_y), true)
-        } else {
-            (from_x.min(to_x) + 1, from_x.max(to_x), false)
-        };
-
-        // Check if path is clear
-        for i in start..end {
-            if is_vertical {
-                if self.squares[i][from_x].is_some() {
-                    return false;
-                }
-            } else {
-                if self.squares[from_y][i].is_some() {
-                    return false;
-                }
-            }
+// `rustchess perft <fen|startpos> <depth>`: prints the node count below
+// each legal move and the total, like the `go perft` command of other engines
+fn run_perft(args: &[String]) {
+    let usage = "Usage: rustchess perft <fen|startpos> <depth>";
+    let (depth, fen) = match args.split_last() {
+        Some((depth, fen)) if !fen.is_empty() => (depth, fen.join(" ")),
+        _ => {
+            eprintln!("{}", usage);
+            return;
         }
-        true
-    }
-
-    fn is_valid_knight_move(&self, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-        let dx = (to_x as i32 - from_x as i32).abs();
-        let dy = (to_y as i32 - from_y as i32).abs();
-        (dx == 2 && dy == 1) || (dx == 1 && dy == 2)
-    }
+    };
 
-    fn is_valid_bishop_move(&self, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-        let dx = (to_x as i32 - from_x as i32).abs();
-        let dy = (to_y as i32 - from_y as i32).abs();
-        
-        if dx != dy {
-            return false;
+    let depth: u32 = match depth.parse() {
+        Ok(depth) => depth,
+        Err(_) => {
+            eprintln!("{}", usage);
+            return;
         }
+    };
 
-        let x_direction = if to_x > from_x { 1 } else { -1 };
-        let y_direction = if to_y > from_y { 1 } else { -1 };
-
-        let mut x = from_x as i32 + x_direction;
-        let mut y = from_y as i32 + y_direction;
-
-        while x != to_x as i32 {
-            if Board::is_valid_position(x, y) && self.squares[y as usize][x as usize].is_some() {
-                return false;
+    let mut board = if fen == "startpos" {
+        Board::new()
+    } else {
+        match Board::from_fen(&fen) {
+            Ok(board) => board,
+            Err(err) => {
+                eprintln!("Invalid FEN: {}", err);
+                return;
             }
-            x += x_direction;
-            y += y_direction;
         }
-        true
-    }
+    };
 
-    fn is_valid_queen_move(&self, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-        self.is_valid_rook_move(from_x, from_y, to_x, to_y) || 
-        self.is_valid_bishop_move(from_x, from_y, to_x, to_y)
+    let start = Instant::now();
+    let mut total = if depth == 0 { 1 } else { 0 };
+    for (mv, nodes) in board.divide(depth) {
+        println!("{}: {}", mv, nodes);
+        total += nodes;
     }
 
-    fn is_valid_king_move(&self, from_x: usize, from_y: usize, to_x: usize, to_y: usize) -> bool {
-        let dx = (to_x as i32 - from_x as i32).abs();
-        let dy = (to_y as i32 - from_y as i32).abs();
-        dx <= 1 && dy <= 1
-    }
+    println!();
+    println!("Nodes searched: {}", total);
+    println!("Time: {:.3}s", start.elapsed().as_secs_f64());
+}
 
-    fn make_move(&mut self, from: &str, to: &str) -> bool {
-        if !self.is_valid_move(from, to) {
-            return false;
+// `rustchess epd <file>`: lets the AI play every position of an EPD test
+// suite and counts how many it solves, by a "bm" move or by avoiding "am" moves
+fn run_epd(args: &[String]) {
+    let path = match args {
+        [path] => path,
+        _ => {
+            eprintln!("Usage: rustchess epd <file>");
+            return;
         }
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", path, err);
+            return;
+        }
+    };
 
-        let (from_x, from_y) = match parse_position(from) {
-            (Some(x), Some(y)) => (x, y),
-            _ => return false,
+    let start = Instant::now();
+    let (mut solved, mut total) = (0, 0);
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let epd = match Epd::parse(line) {
+            Ok(epd) => epd,
+            Err(err) => {
+                eprintln!("Line {}: {}", i + 1, err);
+                continue;
+            }
         };
+        let (best, avoid) = (epd.best_moves(), epd.avoid_moves());
+        if best.is_empty() && avoid.is_empty() {
+            continue;
+        }
 
-        let (to_x, to_y) = match parse_position(to) {
-            (Some(x), Some(y)) => (x, y),
-            _ => return false,
+        let id = epd.id().map_or_else(|| format!("line {}", i + 1), str::to_string);
+        let ai = ChessAI::new(epd.board.side_to_move());
+        let Some(mv) = ai.make_move(&epd.board) else {
+            println!("{}: no legal move", id);
+            continue;
         };
+        let ok = (best.is_empty() || best.contains(&mv)) && !avoid.contains(&mv);
 
-        self.squares[to_y][to_x] = self.squares[from_y][from_x];
-        self.squares[from_y][from_x] = None;
-        
-        self.check_pawn_promotion(to_x, to_y);
-        true
-    }
-
-    fn check_pawn_promotion(&mut self, to_x: usize, to_y: usize) {
-        if let Some(piece) = self.squares[to_y][to_x] {
-            if piece.piece_type == PieceType::Pawn {
-                // Check if pawn reached the opposite end
-                if (piece.color == PieceColor::White && to_y == 0) ||
-                   (piece.color == PieceColor::Black && to_y == 7) {
-                    // Automatically promote to Queen
-                    self.squares[to_y][to_x] = Some(Piece {
-                        piece_type: PieceType::Queen,  // Always promote to Queen for simplicity
-                        color: piece.color,
-                    });
-                }
-            }
+        total += 1;
+        if ok {
+            solved += 1;
         }
+        let expected = ["bm", "am"]
+            .into_iter()
+            .find_map(|opcode| epd.operation(opcode).map(|moves| format!("{} {}", opcode, moves.join(" "))))
+            .unwrap_or_default();
+        println!("{}: {} {} ({})", id, if ok { "ok  " } else { "FAIL" }, mv.to_san(&epd.board), expected);
     }
+
+    println!();
+    println!("Solved {} of {}", solved, total);
+    println!("Time: {:.3}s", start.elapsed().as_secs_f64());
 }
 
-fn parse_position(pos: &str) -> (Option<usize>, Option<usize>) {
-    if pos.len() != 2 {
-        return (None, None);
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|arg| arg == "perft") {
+        run_perft(&args[2..]);
+        return;
     }
-
-    let chars: Vec<char> = pos.chars().collect();
-    let file = chars[0];
-    let rank = chars[1];
-
-    if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
-        return (None, None);
+    if args.get(1).is_some_and(|arg| arg == "epd") {
+        run_epd(&args[2..]);
+        return;
     }
-
-    let x = (file as u8 - b'a') as usize;
-    let y = (8 - (rank as u8 - b'0')) as usize;
-
-    (Some(x), Some(y))
-}
-
-struct ChessAI {
-    color: PieceColor,
-}
-
-impl ChessAI {
-    const MAX_DEPTH: i32 = 3;  // Increase for stronger but slower AI
-
-    fn new(color: PieceColor) -> Self {
-        ChessAI { color }
+    // `rustchess --uci` talks UCI to a GUI instead of playing in the terminal
+    if args.iter().any(|arg| arg == "--uci") {
+        rustchess::uci::run().await;
+        return;
     }
-
-    fn evaluate_position(&self, board: &Board) -> i32 {
-        let mut score = 0;
-        
-        // Piece position tables for improved evaluation
-        let pawn_position = [
-            0,  0,  0,  0,  0,  0,  0,  0,
-            50, 50, 50, 50, 50, 50, 50, 50,
-            10, 10, 20, 30, 30, 20, 10, 10,
-            5,  5, 10, 25, 25, 10,  5,  5,
-            0,  0,  0, 20, 20,  0,  0,  0,
-            5, -5,-10,  0,  0,-10, -5,  5,
-            5, 10, 10,-20,-20, 10, 10,  5,
-            0,  0,  0,  0,  0,  0,  0,  0
-        ];
-
-        let knight_position = [
-            -50,-40,-30,-30,-30,-30,-40,-50,
-            -40,-20,  0,  0,  0,  0,-20,-40,
-            -30,  0, 10, 15, 15, 10,  0,-30,
-            -30,  5, 15, 20, 20, 15,  5,-30,
-            -30,  0, 15, 20, 20, 15,  0,-30,
-            -30,  5, 10, 15, 15, 10,  5,-30,
-            -40,-20,  0,  5,  5,  0,-20,-40,
-            -50,-40,-30,-30,-30,-30,-40,-50
-        ];
-
-        let bishop_position = [
-            -20,-10,-10,-10,-10,-10,-10,-20,
-            -10,  0,  0,  0,  0,  0,  0,-10,
-            -10,  0,  5, 10, 10,  5,  0,-10,
-            -10,  5,  5, 10, 10,  5,  5,-10,
-            -10,  0, 10, 10, 10, 10,  0,-10,
-            -10, 10, 10, 10, 10, 10, 10,-10,
-            -10,  5,  0,  0,  0,  0,  5,-10,
-            -20,-10,-10,-10,-10,-10,-10,-20
-        ];
-
-        let rook_position = [
-            0,  0,  0,  0,  0,  0,  0,  0,
-            5, 10, 10, 10, 10, 10, 10,  5,
-            -5,  0,  0,  0,  0,  0,  0, -5,
-            -5,  0,  0,  0,  0,  0,  0, -5,
-            -5,  0,  0,  0,  0,  0,  0, -5,
-            -5,  0,  0,  0,  0,  0,  0, -5,
-            -5,  0,  0,  0,  0,  0,  0, -5,
-            0,  0,  0,  5,  5,  0,  0,  0
-        ];
-
-        let queen_position = [
-            -20,-10,-10, -5, -5,-10,-10,-20,
-            -10,  0,  0,  0,  0,  0,  0,-10,
-            -10,  0,  5,  5,  5,  5,  0,-10,
-            -5,  0,  5,  5,  5,  5,  0, -5,
-            0,  0,  5,  5,  5,  5,  0, -5,
-            -10,  5,  5,  5,  5,  5,  0,-10,
-            -10,  0,  5,  0,  0,  0,  0,-10,
-            -20,-10,-10, -5, -5,-10,-10,-20
-        ];
-
-        let king_position = [
-            -30,-40,-40,-50,-50,-40,-40,-30,
-            -30,-40,-40,-50,-50,-40,-40,-30,
-            -30,-40,-40,-50,-50,-40,-40,-30,
-            -30,-40,-40,-50,-50,-40,-40,-30,
-            -20,-30,-30,-40,-40,-30,-30,-20,
-            -10,-20,-20,-20,-20,-20,-20,-10,
-            20, 20,  0,  0,  0,  0, 20, 20,
-            20, 30, 10,  0,  0, 10, 30, 20
-        ];
-
-        for i in 0..8 {
-            for j in 0..8 {
-                if let Some(piece) = board.squares[i][j] {
-                    let position_value = match piece.piece_type {
-                        PieceType::Pawn => {
-                            let idx = if piece.color == self.color {
-                                i * 8 + j
-                            } else {
-                                (7 - i) * 8 + j
-                            };
-                            pawn_position[idx]
-                        },
-                        PieceType::Knight => {
-                            let idx = if piece.color == self.color {
-                                i * 8 + j
-                            } else {
-                                (7 - i) * 8 + j
-                            };
-                            knight_position[idx]
-                        },
-                        PieceType::Bishop => {
-                            let idx = if piece.color == self.color {
-                                i * 8 + j
-                            } else {
-                                (7 - i) * 8 + j
-                            };
-                            bishop_position[idx]
-                        },
-                        PieceType::Rook => {
-                            let idx = if piece.color == self.color {
-                                i * 8 + j
-                            } else {
-                                (7 - i) * 8 + j
-                            };
-                            rook_position[idx]
-                        },
-                        PieceType::Queen => {
-                            let idx = if piece.color == self.color {
-                                i * 8 + j
-                            } else {
-                                (7 - i) * 8 + j
-                            };
-                            queen_position[idx]
-                        },
-                        PieceType::King => {
-                            let idx = if piece.color == self.color {
-                                i * 8 + j
-                            } else {
-                                (7 - i) * 8 + j
-                            };
-                            king_position[idx]
-                        },
-                    };
-
-                    let piece_value = match piece.piece_type {
-                        PieceType::Pawn => 100,
-                        PieceType::Knight => 320,
-                        PieceType::Bishop => 330,
-                        PieceType::Rook => 500,
-                        PieceType::Queen => 900,
-                        PieceType::King => 20000,
-                    };
-
-                    // Add mobility bonus
-                    let mobility_bonus = if piece.color == self.color {
-                        self.count_legal_moves(board, i, j) as i32 * 10
-                    } else {
-                        -(self.count_legal_moves(board, i, j) as i32 * 10)
-                    };
-
-                    if piece.color == self.color {
-                        score += piece_value + position_value + mobility_bonus;
-                    } else {
-                        score -= piece_value + position_value - mobility_bonus;
-                    }
-                }
-            }
-        }
-        score
+    // `rustchess --xboard` does the same for XBoard and other CECP tools
+    if args.iter().any(|arg| arg == "--xboard") {
+        rustchess::xboard::run().await;
+        return;
     }
 
-    fn count_legal_moves(&self, board: &Board, i: usize, j: usize) -> usize {
-        let from = format!("{}{}", (b'a' + j as u8) as char, 8 - i);
-        let mut count = 0;
-        
-        for to_i in 0..8 {
-            for to_j in 0..8 {
-                let to = format!("{}{}", (b'a' + to_j as u8) as char, 8 - to_i);
-                if board.is_valid_move(&from, &to) {
-                    count += 1;
-                }
+    // `rustchess --fen "<FEN>"` starts from the given position
+    let mut board = match args.iter().position(|arg| arg == "--fen") {
+        Some(i) => match args.get(i + 1).map(|fen| Board::from_fen(fen)) {
+            Some(Ok(board)) => board,
+            Some(Err(err)) => {
+                eprintln!("Invalid FEN: {}", err);
+                return;
             }
-        }
-        count
-    }
-
-    fn get_all_possible_moves(&self, board: &Board) -> Vec<(String, String)> {
-        let mut moves = Vec::new();
-        for i in 0..8 {
-            for j in 0..8 {
-                if let Some(piece) = board.squares[i][j] {
-                    if piece.color == self.color {
-                        let from = format!("{}{}", 
-                            (b'a' + j as u8) as char,
-                            8 - i
-                        );
-                        
-                        for to_i in 0..8 {
-                            for to_j in 0..8 {
-                                let to = format!("{}{}", 
-                                    (b'a' + to_j as u8) as char,
-                                    8 - to_i
-                                );
-                                if board.is_valid_move(&from, &to) {
-                                    moves.push((from.clone(), to));
-                                }
-                            }
-                        }
-                    }
-                }
+            None => {
+                eprintln!("Usage: rustchess --fen \"<FEN>\"");
+                return;
             }
-        }
-        moves
-    }
-
-    fn minimax(&self, board: &Board, depth: i32, alpha: i32, beta: i32, maximizing: bool) -> i32 {
-        if depth == 0 {
-            return self.evaluate_position(board);
-        }
-
-        let moves = self.get_all_possible_moves(board);
-        if moves.is_empty() {
-            return if maximizing { -1000 } else { 1000 };
-        }
+        },
+        None => Board::new(),
+    };
 
-        if maximizing {
-            let mut max_eval = i32::MIN;
-            for (from, to) in moves {
-                let mut new_board = board.clone();
-                if new_board.make_move(&from, &to) {
-                    let eval = self.minimax(&new_board, depth - 1, alpha, beta, false);
-                    max_eval = max_eval.max(eval);
-                    if max_eval >= beta {
-                        break;
-                    }
-                }
+    println!("Welcome to RustChess!");
+    let game_mode = loop {
+        println!("1. Play against AI");
+        println!("2. Play against another player");
+        println!("3. Watch AI vs AI");
+        println!("4. Set up a position from FEN");
+        println!("5. Set up a random Chess960 position");
+        println!("6. Replay a game from a PGN file");
+        let Some(input) = read_line("Choose game mode (1-6): ") else {
+            return;
+        };
+        match input.as_str() {
+            "4" => {}
+            "5" => {
+                let number = rand::thread_rng().gen_range(0..960);
+                board = Board::chess960(number);
+                println!("Chess960 position {} loaded: {}", number, board.to_fen());
+                display(&board);
+                continue;
             }
-            max_eval
-        } else {
-            let mut min_eval = i32::MAX;
-            for (from, to) in moves {
-                let mut new_board = board.clone();
-                if new_board.make_move(&from, &to) {
-                    let eval = self.minimax(&new_board, depth - 1, alpha, beta, true);
-                    min_eval = min_eval.min(eval);
-                    if min_eval <= alpha {
-                        break;
-                    }
-                }
+            "6" => {
+                replay_pgn();
+                continue;
             }
-            min_eval
+            _ => break input,
         }
-    }
 
-    fn make_move(&self, board: &Board) -> Option<(String, String)> {
-        let moves = self.get_all_possible_moves(board);
-        let mut best_move = None;
-        let mut best_eval = i32::MIN;
-
-        for (from, to) in moves {
-            let mut new_board = board.clone();
-            if new_board.make_move(&from, &to) {
-                let eval = self.minimax(&new_board, ChessAI::MAX_DEPTH - 1, i32::MIN, i32::MAX, false);
-                if eval > best_eval {
-                    best_eval = eval;
-                    best_move = Some((from, to));
-                }
+        let Some(fen) = read_line("Enter FEN: ") else {
+            return;
+        };
+        match Board::from_fen(&fen) {
+            Ok(new_board) => {
+                board = new_board;
+                println!("Position loaded.");
+                display(&board);
             }
+            Err(err) => println!("Invalid FEN: {}", err),
         }
-
-        best_move
-    }
-}
-
-#[tokio::main]
-async fn main() {
-    println!("Welcome to RustChess!");
-    println!("1. Play against AI");
-    println!("2. Play against another player");
-    println!("3. Watch AI vs AI");
-    print!("Choose game mode (1-3): ");
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let game_mode = input.trim();
-
-    let mut board = Board::new();
-    let mut current_turn = PieceColor::White;
+    };
     
     // Initialize AIs based on game mode
-    let (white_ai, black_ai) = match game_mode {
+    let (white_ai, black_ai) = match game_mode.as_str() {
         "1" => {
             println!("You'll play as White against the AI (Black)");
             (None, Some(ChessAI::new(PieceColor::Black)))
@@ -698,24 +356,40 @@ async fn main() {
         _ => (None, None)
     };
 
+    let player_name = |ai: &Option<ChessAI>| if ai.is_some() { "RustChess AI" } else { "Human" }.to_string();
+    let tags = PgnTags {
+        event: "RustChess game".to_string(),
+        site: "RustChess".to_string(),
+        white: player_name(&white_ai),
+        black: player_name(&black_ai),
+        ..PgnTags::default()
+    };
+
+    let mut game = Game::new(board);
     loop {
-        board.display();
+        let board = game.board();
+        display(board);
         
-        let turn_str = if current_turn == PieceColor::White {
-            "White"
-        } else {
-            "Black"
-        };
-
-        // Check for checkmate and stalemate
-        if board.is_checkmate(current_turn) {
-            println!("Checkmate! {} wins!", if current_turn == PieceColor::White { "Black" } else { "White" });
-            break;
-        }
+        let current_turn = board.side_to_move();
+        let turn_str = color_name(current_turn);
 
-        if board.is_stalemate(current_turn) {
-            println!("Stalemate! The game is a draw!");
-            break;
+        // Checkmate, stalemate and automatic draws end the game by themselves
+        match game.termination() {
+            Some(Termination::Checkmate) => {
+                println!("Checkmate! {} wins!", color_name(current_turn.opposite()));
+                break;
+            }
+            Some(Termination::Stalemate) => {
+                println!("Stalemate! The game is a draw!");
+                break;
+            }
+            Some(termination) => {
+                // The board tells fivefold repetition and the 75-move rule apart
+                let reason = board.automatic_draw().map_or(termination.description(), |reason| reason.description());
+                println!("Draw by {}!", reason);
+                break;
+            }
+            None => {}
         }
 
         // Show if the king is in check
@@ -730,21 +404,27 @@ async fn main() {
         };
 
         if let Some(ai) = current_ai {
+            // The AI takes a draw whenever it is not better
+            if let Some(reason) = board.claimable_draw() {
+                if ai.evaluate_position(board) <= 0 {
+                    println!("{} AI claims a draw by {}!", turn_str, reason.description());
+                    game.claim_draw();
+                    break;
+                }
+            }
+
             println!("{} AI is thinking...", turn_str);
-            if let Some((from, to)) = ai.make_move(&board) {
-                println!("{} AI moves: {} to {}", turn_str, from, to);
+            if let Some(mv) = ai.make_move(board) {
+                println!("{} AI plays {}", turn_str, mv.to_san(board));
                 
                 // In AI vs AI mode, wait for 1 second before next move
                 if game_mode == "3" {
                     sleep(Duration::from_secs(1)).await;
                 }
 
-                if board.make_move(&from, &to) {
-                    current_turn = if current_turn == PieceColor::White {
-                        PieceColor::Black
-                    } else {
-                        PieceColor::White
-                    };
+                if let Err(err) = game.make_move(mv) {
+                    println!("{} AI made an illegal move: {}", turn_str, err);
+                    break;
                 }
             } else {
                 println!("AI couldn't find a valid move!");
@@ -754,31 +434,100 @@ async fn main() {
         }
         
         // Handle human moves
-        print!("{}'s turn (e.g., 'e2 e4' or 'quit'): ", turn_str);
-        io::stdout().flush().unwrap();
+        let claimable_draw = board.claimable_draw();
+        if let Some(reason) = claimable_draw {
+            println!("You may claim a draw by {} by typing 'draw'.", reason.description());
+        }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
+        let prompt = format!("{}'s turn (e.g., 'e4', 'Nf3', 'e2 e4', 'moves', 'save <file>', 'fen', 'resign' or 'quit'): ", turn_str);
+        let Some(line) = read_line(&prompt) else {
+            break;
+        };
+        let input = line.as_str();
 
         if input == "quit" {
             break;
         }
 
-        let parts: Vec<&str> = input.split_whitespace().collect();
-        if parts.len() != 2 {
-            println!("Invalid input format. Use 'from to' (e.g., 'e2 e4')");
+        if input == "fen" {
+            println!("{}", board.to_fen());
             continue;
         }
 
-        if board.make_move(parts[0], parts[1]) {
-            current_turn = if current_turn == PieceColor::White {
-                PieceColor::Black
+        if input == "moves" {
+            if game.moves().is_empty() {
+                println!("No moves yet.");
             } else {
-                PieceColor::White
+                println!("{}", game.movetext());
+            }
+            continue;
+        }
+
+        if let Some(path) = input.strip_prefix("save ").map(str::trim) {
+            match save_game(&game, &tags, path, false) {
+                Ok(()) => println!("Game saved to {}", path),
+                Err(err) => println!("Could not save the game to {}: {}", path, err),
+            }
+            continue;
+        }
+
+        if input == "resign" {
+            println!("{} resigns. {} wins!", turn_str, color_name(current_turn.opposite()));
+            game.resign(current_turn);
+            break;
+        }
+
+        if input == "draw" {
+            match claimable_draw {
+                Some(reason) => {
+                    println!("{} claims a draw by {}!", turn_str, reason.description());
+                    game.claim_draw();
+                    break;
+                }
+                None => {
+                    println!("No draw can be claimed in this position.");
+                    continue;
+                }
+            }
+        }
+
+        let mut mv = match board.parse_move(input) {
+            Ok(mv) => mv,
+            Err(MoveError::BadSyntax) => {
+                println!("Invalid input format: {}", MoveError::BadSyntax);
+                continue;
+            }
+            Err(err) => {
+                println!("Illegal move: {}", err);
+                continue;
+            }
+        };
+
+        if mv.promotion.is_none() && board.is_promotion(mv.from, mv.to) && board.check_move(mv).is_ok() {
+            mv.promotion = loop {
+                let Some(choice) = read_line("Promote to (q/r/b/n): ") else {
+                    break None;
+                };
+                if let Some(piece_type) = parse_promotion(&choice) {
+                    break Some(piece_type);
+                }
             };
-        } else {
-            println!("Invalid move!");
+            // The input ended before a piece was chosen
+            if mv.promotion.is_none() {
+                break;
+            }
+        }
+
+        if let Err(err) = game.make_move(mv) {
+            println!("Illegal move: {}", err);
+        }
+    }
+
+    // Keep a record of every game that got past its first move
+    if !game.moves().is_empty() {
+        match save_game(&game, &tags, GAMES_FILE, true) {
+            Ok(()) => println!("Game saved to {}", GAMES_FILE),
+            Err(err) => println!("Could not save the game to {}: {}", GAMES_FILE, err),
         }
     }
 }
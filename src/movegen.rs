@@ -0,0 +1,562 @@
+//! Attack tables, move generation and legality, and perft.
+
+use crate::board::{bitboard_squares, Bitboard, Board, Move, MoveError, MoveKind, Piece, PieceColor, PieceType, Square, RANK_1, RANK_8};
+
+// Squares reached by single (file, rank) steps from each square
+const fn step_attacks(steps: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut i = 0;
+        while i < steps.len() {
+            let file = (square % 8) as i32 + steps[i].0;
+            let rank = (square / 8) as i32 + steps[i].1;
+            if 0 <= file && file < 8 && 0 <= rank && rank < 8 {
+                table[square] |= 1 << (rank * 8 + file);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+const KNIGHT_ATTACKS: [Bitboard; 64] =
+    step_attacks(&[(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)]);
+const KING_ATTACKS: [Bitboard; 64] =
+    step_attacks(&[(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)]);
+// Indexed by the color of the attacking pawn
+pub(crate) const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [step_attacks(&[(-1, 1), (1, 1)]), step_attacks(&[(-1, -1), (1, -1)])];
+
+// Sliding directions as (file, rank) steps; the first four move towards higher squares
+const DIRECTIONS: [(i32, i32); 8] = [(0, 1), (1, 0), (1, 1), (-1, 1), (0, -1), (-1, 0), (-1, -1), (1, -1)];
+
+// Every square from each square to the edge of the board in each direction
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut file = (square % 8) as i32 + DIRECTIONS[dir].0;
+            let mut rank = (square / 8) as i32 + DIRECTIONS[dir].1;
+            while 0 <= file && file < 8 && 0 <= rank && rank < 8 {
+                table[dir][square] |= 1 << (rank * 8 + file);
+                file += DIRECTIONS[dir].0;
+                rank += DIRECTIONS[dir].1;
+            }
+            square += 1;
+        }
+        dir += 1;
+    }
+    table
+}
+
+const RAYS: [[Bitboard; 64]; 8] = ray_table();
+
+// A ray stops at (and includes) the first occupied square
+fn ray_attacks(dir: usize, square: Square, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[dir][square.index()];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+
+    let nearest = if dir < 4 {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[dir][nearest as usize]
+}
+
+fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(0, square, occupied)
+        | ray_attacks(1, square, occupied)
+        | ray_attacks(4, square, occupied)
+        | ray_attacks(5, square, occupied)
+}
+
+fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(2, square, occupied)
+        | ray_attacks(3, square, occupied)
+        | ray_attacks(6, square, occupied)
+        | ray_attacks(7, square, occupied)
+}
+
+// Attacks of every piece except pawns, which depend on their color
+fn piece_attacks(piece_type: PieceType, square: Square, occupied: Bitboard) -> Bitboard {
+    match piece_type {
+        PieceType::Knight => KNIGHT_ATTACKS[square.index()],
+        PieceType::Bishop => bishop_attacks(square, occupied),
+        PieceType::Rook => rook_attacks(square, occupied),
+        PieceType::Queen => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+        PieceType::King => KING_ATTACKS[square.index()],
+        PieceType::Pawn => 0,
+    }
+}
+
+// Squares strictly between two squares on a common line, or none if
+// they do not share one
+fn between(a: Square, b: Square) -> Bitboard {
+    for dir in 0..8 {
+        if RAYS[dir][a.index()] & b.bit() != 0 {
+            return RAYS[dir][a.index()] & RAYS[(dir + 4) % 8][b.index()];
+        }
+    }
+    0
+}
+
+// The whole line through two squares, from edge to edge
+fn line_through(a: Square, b: Square) -> Bitboard {
+    for dir in 0..8 {
+        if RAYS[dir][a.index()] & b.bit() != 0 {
+            return RAYS[dir][a.index()] | RAYS[(dir + 4) % 8][a.index()] | a.bit();
+        }
+    }
+    0
+}
+
+// Squares a pawn on `from` can be pushed to, ignoring blockers
+fn pawn_push(from: Square, color: PieceColor) -> Bitboard {
+    match color {
+        PieceColor::White => from.bit() << 8,
+        PieceColor::Black => from.bit() >> 8,
+    }
+}
+
+// Adds a pawn move, as all four promotions if it reaches the last rank
+fn push_pawn_move(from: Square, to: Square, kind: MoveKind, moves: &mut Vec<Move>) {
+    if to.bit() & (RANK_1 | RANK_8) == 0 {
+        moves.push(Move { from, to, promotion: None, kind });
+        return;
+    }
+    for piece_type in [PieceType::Queen, PieceType::Knight, PieceType::Rook, PieceType::Bishop] {
+        moves.push(Move { from, to, promotion: Some(piece_type), kind });
+    }
+}
+
+impl Board {
+    /// Pieces of `color` attacking `square`, given the occupancy `occupied`
+    pub fn attackers_to(&self, square: Square, color: PieceColor, occupied: Bitboard) -> Bitboard {
+        let i = square.index();
+        let diagonal = self.pieces[PieceType::Bishop as usize] | self.pieces[PieceType::Queen as usize];
+        let straight = self.pieces[PieceType::Rook as usize] | self.pieces[PieceType::Queen as usize];
+
+        // A pawn attacks this square from wherever an enemy pawn here would attack
+        let attackers = (PAWN_ATTACKS[color.opposite() as usize][i] & self.pieces[PieceType::Pawn as usize])
+            | (KNIGHT_ATTACKS[i] & self.pieces[PieceType::Knight as usize])
+            | (KING_ATTACKS[i] & self.pieces[PieceType::King as usize])
+            | (bishop_attacks(square, occupied) & diagonal)
+            | (rook_attacks(square, occupied) & straight);
+
+        attackers & self.colors[color as usize]
+    }
+
+    /// Whether any piece of `by_color` attacks `square`
+    pub fn is_square_attacked(&self, square: Square, by_color: PieceColor) -> bool {
+        self.attackers_to(square, by_color, self.occupied()) != 0
+    }
+
+    /// Pieces of either color attacking `square`
+    pub fn attackers_of(&self, square: Square) -> Bitboard {
+        let occupied = self.occupied();
+        self.attackers_to(square, PieceColor::White, occupied) | self.attackers_to(square, PieceColor::Black, occupied)
+    }
+
+    // Enemy pieces giving check to the king of `color`
+    fn king_attackers(&self, color: PieceColor) -> Bitboard {
+        match self.king_square(color) {
+            Some(king) => self.attackers_of(king) & self.colors[color.opposite() as usize],
+            None => 0,
+        }
+    }
+
+    /// Pieces giving check to the side to move
+    pub fn checkers(&self) -> Bitboard {
+        self.king_attackers(self.side_to_move)
+    }
+
+    /// Pieces of `color` that stand alone between their king and an enemy
+    /// slider, and so may only move along that line
+    pub fn pinned_pieces(&self, color: PieceColor) -> Bitboard {
+        let king = match self.king_square(color) {
+            Some(king) => king,
+            None => return 0,
+        };
+        let them = color.opposite();
+        let diagonal = self.pieces_of(PieceType::Bishop, them) | self.pieces_of(PieceType::Queen, them);
+        let straight = self.pieces_of(PieceType::Rook, them) | self.pieces_of(PieceType::Queen, them);
+        let snipers = (bishop_attacks(king, 0) & diagonal) | (rook_attacks(king, 0) & straight);
+
+        let mut pinned = 0;
+        for sniper in bitboard_squares(snipers) {
+            let blockers = between(king, sniper) & self.occupied();
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & self.colors[color as usize];
+            }
+        }
+        pinned
+    }
+
+    /// Whether the king of `color` is attacked
+    pub fn is_king_in_check(&self, color: PieceColor) -> bool {
+        self.king_attackers(color) != 0
+    }
+
+    // Captures, including en passant and capturing promotions, of the piece on `from`
+    fn generate_captures(&self, from: Square, moves: &mut Vec<Move>) {
+        let piece = match self.piece_at(from) {
+            Some(p) => p,
+            None => return,
+        };
+        let enemies = self.colors[piece.color.opposite() as usize];
+
+        if piece.piece_type == PieceType::Pawn {
+            let mut targets = PAWN_ATTACKS[piece.color as usize][from.index()] & enemies;
+            for to in bitboard_squares(targets) {
+                push_pawn_move(from, to, MoveKind::Capture, moves);
+            }
+
+            // The en passant square is only ever on the rank in front of the enemy pawns
+            let en_passant_rank = if piece.color == PieceColor::White { 5 } else { 2 };
+            targets = match self.en_passant.filter(|s| s.rank() == en_passant_rank) {
+                Some(square) => PAWN_ATTACKS[piece.color as usize][from.index()] & square.bit(),
+                None => 0,
+            };
+            for to in bitboard_squares(targets) {
+                moves.push(Move { from, to, promotion: None, kind: MoveKind::EnPassant });
+            }
+            return;
+        }
+
+        let targets = piece_attacks(piece.piece_type, from, self.occupied()) & enemies;
+        for to in bitboard_squares(targets) {
+            moves.push(Move { from, to, promotion: None, kind: MoveKind::Capture });
+        }
+    }
+
+    // Pawn pushes onto the last rank; capturing promotions count as captures
+    fn generate_promotions(&self, from: Square, moves: &mut Vec<Move>) {
+        let piece = match self.piece_at(from) {
+            Some(p) if p.piece_type == PieceType::Pawn => p,
+            _ => return,
+        };
+        let last_rank = if piece.color == PieceColor::White { RANK_8 } else { RANK_1 };
+
+        let targets = pawn_push(from, piece.color) & !self.occupied() & last_rank;
+        for to in bitboard_squares(targets) {
+            push_pawn_move(from, to, MoveKind::Quiet, moves);
+        }
+    }
+
+    // Non-capturing moves other than promotions, including double pawn pushes and castling
+    fn generate_quiets(&self, from: Square, moves: &mut Vec<Move>) {
+        let piece = match self.piece_at(from) {
+            Some(p) => p,
+            None => return,
+        };
+        let empty = !self.occupied();
+
+        match piece.piece_type {
+            PieceType::Pawn => {
+                let single = pawn_push(from, piece.color) & empty & !(RANK_1 | RANK_8);
+                for to in bitboard_squares(single) {
+                    moves.push(Move { from, to, promotion: None, kind: MoveKind::Quiet });
+                }
+
+                let start_rank = if piece.color == PieceColor::White { 1 } else { 6 };
+                if single != 0 && from.rank() == start_rank {
+                    let double = pawn_push(Square(single.trailing_zeros() as u8), piece.color) & empty;
+                    for to in bitboard_squares(double) {
+                        moves.push(Move { from, to, promotion: None, kind: MoveKind::DoublePawnPush });
+                    }
+                }
+            }
+            piece_type => {
+                let targets = piece_attacks(piece_type, from, self.occupied()) & empty;
+                for to in bitboard_squares(targets) {
+                    moves.push(Move { from, to, promotion: None, kind: MoveKind::Quiet });
+                }
+
+                if piece_type == PieceType::King {
+                    for kingside in [true, false] {
+                        if let Some(rook) = self.castling_rook(piece.color, kingside) {
+                            if self.can_castle(piece.color, kingside) {
+                                moves.push(Move { from, to: rook, promotion: None, kind: MoveKind::Castle });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Every move of the piece on `from` that follows its movement rules,
+    // whether or not it leaves the own king in check
+    fn pseudo_legal_moves_from(&self, from: Square, moves: &mut Vec<Move>) {
+        self.generate_captures(from, moves);
+        self.generate_promotions(from, moves);
+        self.generate_quiets(from, moves);
+    }
+
+    // Checks a pseudo-legal move of a piece of `color`, given the pieces
+    // pinned to and the pieces checking that color's king
+    fn is_legal(&self, mv: Move, pinned: Bitboard, checkers: Bitboard) -> bool {
+        let piece = match self.piece_at(mv.from) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        // Castling checks all the squares of the king's way itself
+        if mv.kind == MoveKind::Castle {
+            return true;
+        }
+
+        // King moves and en passant, which takes two pieces off the same rank,
+        // are checked against the position after the move
+        if piece.piece_type == PieceType::King || mv.kind == MoveKind::EnPassant {
+            return self.leaves_king_safe(mv.from, mv.to, piece);
+        }
+
+        let king = match self.king_square(piece.color) {
+            Some(king) => king,
+            None => return true,
+        };
+
+        // Against a single check the move has to capture the checker or block it;
+        // against a double check only the king can move
+        if checkers != 0 {
+            if checkers.count_ones() > 1 {
+                return false;
+            }
+            let checker = Square(checkers.trailing_zeros() as u8);
+            if (checkers | between(king, checker)) & mv.to.bit() == 0 {
+                return false;
+            }
+        }
+
+        pinned & mv.from.bit() == 0 || line_through(king, mv.from) & mv.to.bit() != 0
+    }
+
+    /// Legal moves of the piece on `from`, regardless of whose turn it is
+    pub fn legal_moves_from(&self, from: Square) -> Vec<Move> {
+        let color = match self.piece_at(from) {
+            Some(piece) => piece.color,
+            None => return Vec::new(),
+        };
+        let pinned = self.pinned_pieces(color);
+        let checkers = self.king_attackers(color);
+
+        let mut moves = Vec::new();
+        self.pseudo_legal_moves_from(from, &mut moves);
+        moves.retain(|&mv| self.is_legal(mv, pinned, checkers));
+        moves
+    }
+
+    /// Legal moves of the side to move, each promotion once per piece
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let pinned = self.pinned_pieces(self.side_to_move);
+        let checkers = self.checkers();
+
+        let mut moves = Vec::new();
+        for from in bitboard_squares(self.colors[self.side_to_move as usize]) {
+            self.pseudo_legal_moves_from(from, &mut moves);
+        }
+        moves.retain(|&mv| self.is_legal(mv, pinned, checkers));
+        moves
+    }
+
+    /// Whether `mv` is legal here, judged by its squares and promotion alone;
+    /// a promotion without a piece is checked as the queen promotion
+    /// make_move plays
+    pub fn is_valid_move(&self, mv: Move) -> bool {
+        self.legal_moves_from(mv.from).contains(&self.normalize_move(mv))
+    }
+
+    /// Checks a move of the side to move like make_move does, telling what
+    /// is wrong with it if it is illegal
+    pub fn check_move(&self, mv: Move) -> Result<(), MoveError> {
+        let piece = self.piece_at(mv.from).ok_or(MoveError::NoPiece(mv.from))?;
+        if piece.color != self.side_to_move {
+            return Err(MoveError::OpponentPiece(mv.from));
+        }
+        if self.is_valid_move(mv) {
+            return Ok(());
+        }
+        // Diagnosed by its squares, like is_valid_move judges it
+        let mv = self.normalize_move(mv);
+
+        // Castling entered after the rights are gone looks like a two-square
+        // king move from the king's home square
+        let back_rank = if piece.color == PieceColor::White { 0 } else { 7 };
+        if mv.kind == MoveKind::Castle {
+            self.check_castling(piece.color, mv.to.file() > mv.from.file())?;
+        } else if piece.piece_type == PieceType::King
+            && mv.from == Square::new(4, back_rank)
+            && mv.to.rank() == back_rank
+            && mv.from.file().abs_diff(mv.to.file()) == 2
+        {
+            return Err(MoveError::CastlingRightsLost);
+        }
+
+        if mv.kind != MoveKind::Castle && self.colors[piece.color as usize] & mv.to.bit() != 0 {
+            return Err(MoveError::OwnPieceOnTarget(mv.to));
+        }
+        // A pawn's diagonal step onto an empty square that is not the en passant square
+        if mv.kind == MoveKind::EnPassant
+            && self.en_passant != Some(mv.to)
+            && PAWN_ATTACKS[piece.color as usize][mv.from.index()] & mv.to.bit() != 0
+        {
+            return Err(MoveError::NothingToCapture(mv.to));
+        }
+        if let Some(piece_type) = mv.promotion {
+            if !self.is_promotion(mv.from, mv.to) || matches!(piece_type, PieceType::King | PieceType::Pawn) {
+                return Err(MoveError::InvalidPromotion);
+            }
+        }
+
+        // A move that only fails on king safety, being the same move as one
+        // the piece has before legality is checked
+        let mut candidates = Vec::new();
+        self.pseudo_legal_moves_from(mv.from, &mut candidates);
+        let same_move = |candidate: &Move| {
+            candidate.to == mv.to
+                && candidate.promotion == mv.promotion
+                && (candidate.kind == MoveKind::Castle) == (mv.kind == MoveKind::Castle)
+        };
+        if candidates.iter().any(same_move) {
+            return Err(if self.is_king_in_check(piece.color) {
+                MoveError::StillInCheck
+            } else {
+                MoveError::LeavesKingInCheck
+            });
+        }
+
+        // Otherwise the piece either cannot get past something in its way,
+        // or does not move like that at all
+        let reach = match piece.piece_type {
+            PieceType::Pawn => {
+                let start_rank = if piece.color == PieceColor::White { 1 } else { 6 };
+                let single = pawn_push(mv.from, piece.color);
+                match bitboard_squares(single).next() {
+                    Some(next) if mv.from.rank() == start_rank => single | pawn_push(next, piece.color),
+                    _ => single,
+                }
+            }
+            piece_type => piece_attacks(piece_type, mv.from, 0),
+        };
+        if reach & mv.to.bit() != 0 {
+            // A pawn push is also blocked by a piece on the destination
+            let mut in_the_way = between(mv.from, mv.to);
+            if piece.piece_type == PieceType::Pawn {
+                in_the_way |= mv.to.bit();
+            }
+            if let Some(blocker) = bitboard_squares(in_the_way & self.occupied()).next() {
+                return Err(MoveError::PathBlocked(blocker));
+            }
+        }
+        Err(MoveError::IllegalPieceMove(piece.piece_type))
+    }
+
+    // Looks at the position after the move through its occupancy alone,
+    // without playing the move on a copy of the board
+    fn leaves_king_safe(&self, from: Square, to: Square, piece: Piece) -> bool {
+        let mut occupied = (self.occupied() & !from.bit()) | to.bit();
+        let mut captured = to.bit();
+
+        // En passant removes a pawn from beside the destination square
+        if piece.piece_type == PieceType::Pawn && from.file() != to.file() && self.occupied() & to.bit() == 0 {
+            let passed = Square::new(to.file(), from.rank()).bit();
+            occupied &= !passed;
+            captured |= passed;
+        }
+
+        let king = if piece.piece_type == PieceType::King {
+            to
+        } else {
+            match self.king_square(piece.color) {
+                Some(king) => king,
+                None => return true,
+            }
+        };
+
+        self.attackers_to(king, piece.color.opposite(), occupied) & !captured == 0
+    }
+
+    pub(crate) fn castling_rook(&self, color: PieceColor, kingside: bool) -> Option<Square> {
+        let back_rank = if color == PieceColor::White { 0 } else { 7 };
+        self.castling_rights.rook_file(color, kingside).map(|file| Square::new(file, back_rank))
+    }
+
+    fn can_castle(&self, color: PieceColor, kingside: bool) -> bool {
+        self.check_castling(color, kingside).is_ok()
+    }
+
+    fn check_castling(&self, color: PieceColor, kingside: bool) -> Result<(), MoveError> {
+        let (king, rook) = match (self.king_square(color), self.castling_rook(color, kingside)) {
+            (Some(king), Some(rook)) => (king, rook),
+            _ => return Err(MoveError::CastlingRightsLost),
+        };
+        if self.piece_at(rook) != Some(Piece { piece_type: PieceType::Rook, color }) {
+            return Err(MoveError::CastlingRightsLost);
+        }
+
+        let castle = Move { from: king, to: rook, promotion: None, kind: MoveKind::Castle };
+        let (king_to, rook_to) = castle.castling_destinations();
+
+        // Apart from the king and rook themselves, every square either of
+        // them crosses or lands on must be empty
+        let path = between(king, king_to) | king_to.bit() | between(rook, rook_to) | rook_to.bit();
+        let others = self.occupied() & !king.bit() & !rook.bit();
+        if let Some(blocker) = bitboard_squares(path & others).next() {
+            return Err(MoveError::PathBlocked(blocker));
+        }
+
+        // The king may not castle out of or through check, nor into it once
+        // the rook has moved out of the way of a rook or queen behind it
+        let them = color.opposite();
+        let king_path = king.bit() | between(king, king_to);
+        if bitboard_squares(king_path).any(|square| self.is_square_attacked(square, them))
+            || self.attackers_to(king_to, them, others | king_to.bit() | rook_to.bit()) != 0
+        {
+            return Err(MoveError::CastlingThroughCheck);
+        }
+        Ok(())
+    }
+
+    /// Number of leaf nodes in the tree of legal moves of the given depth
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for mv in moves {
+            let undo = self.play_move(mv);
+            nodes += self.perft(depth - 1);
+            self.unmake_move(undo);
+        }
+        nodes
+    }
+
+    /// Perft split up by the first move, for comparing against other move generators
+    pub fn divide(&mut self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        let mut results = Vec::new();
+        for mv in self.legal_moves() {
+            let undo = self.play_move(mv);
+            results.push((mv, self.perft(depth - 1)));
+            self.unmake_move(undo);
+        }
+        results
+    }
+}
@@ -0,0 +1,491 @@
+//! FEN and the move and piece notation used by the terminal game.
+
+use crate::board::{bitboard_squares, Board, Move, MoveError, MoveKind, Piece, PieceColor, PieceType, Square, RANK_1, RANK_8};
+use std::fmt;
+
+/// Why a FEN string could not be read
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    BadRankLength(usize),
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    KingCount(PieceColor, usize),
+    PawnOnBackRank(Square),
+    OpponentInCheck,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(n) => write!(f, "expected 4 or 6 space-separated fields, found {}", n),
+            FenError::WrongRankCount(n) => write!(f, "piece placement must have 8 ranks separated by '/', found {}", n),
+            FenError::BadRankLength(rank) => write!(f, "rank {} does not describe exactly 8 squares", rank),
+            FenError::InvalidPiece(c) => write!(f, "'{}' is not a piece letter (use PNBRQK or pnbrqk)", c),
+            FenError::InvalidSideToMove(s) => write!(f, "side to move must be 'w' or 'b', found '{}'", s),
+            FenError::InvalidCastling(s) => write!(f, "castling availability must be '-' or a subset of 'KQkq', found '{}'", s),
+            FenError::InvalidEnPassant(s) => write!(f, "en passant square must be '-' or a square on the 3rd/6th rank matching the side to move, found '{}'", s),
+            FenError::InvalidHalfmoveClock(s) => write!(f, "halfmove clock must be a non-negative number, found '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "fullmove number must be a positive number, found '{}'", s),
+            FenError::KingCount(color, n) => write!(f, "{:?} must have exactly one king, found {}", color, n),
+            FenError::PawnOnBackRank(square) => write!(f, "pawn on {} cannot stand on the first or last rank", square),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl Board {
+    /// Reads a position from FEN, X-FEN or Shredder-FEN; the move counters may be left out
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        // The move counters are optional, as in EPD
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+
+        let mut board = Board::empty();
+
+        // Piece placement, from rank 8 down to rank 1
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i;
+            let mut file = 0;
+            for c in rank_str.chars() {
+                if let Some(empty) = c.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                    file += empty as usize;
+                } else {
+                    let piece = piece_from_char(c).ok_or(FenError::InvalidPiece(c))?;
+                    if file < 8 {
+                        board.set_piece(Square::new(file, rank), Some(piece));
+                    }
+                    file += 1;
+                }
+                if file > 8 {
+                    return Err(FenError::BadRankLength(rank + 1));
+                }
+            }
+            if file != 8 {
+                return Err(FenError::BadRankLength(rank + 1));
+            }
+        }
+
+        board.side_to_move = match fields[1] {
+            "w" => PieceColor::White,
+            "b" => PieceColor::Black,
+            other => return Err(FenError::InvalidSideToMove(other.to_string())),
+        };
+
+        // Besides KQkq this takes the rook files of Shredder-FEN (HAha) and
+        // X-FEN, which uses them only for a rook that is not the outermost one
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+                let (kingside, rook_file) = match c.to_ascii_lowercase() {
+                    'k' => (true, board.outermost_rook_file(color, true)),
+                    'q' => (false, board.outermost_rook_file(color, false)),
+                    file @ 'a'..='h' => {
+                        let file = file as usize - 'a' as usize;
+                        let king_file = board.king_square(color).map_or(4, |king| king.file());
+                        (file > king_file, Some(file))
+                    }
+                    _ => return Err(FenError::InvalidCastling(fields[2].to_string())),
+                };
+                let right = board.castling_rights.rook_file_mut(color, kingside);
+                if right.is_some() {
+                    return Err(FenError::InvalidCastling(fields[2].to_string()));
+                }
+                // With no rook to castle with, the right falls back to the
+                // standard file and is dropped as stale by the validation
+                *right = rook_file.or(Some(if kingside { 7 } else { 0 }));
+            }
+        }
+
+        if fields[3] != "-" {
+            let expected_rank = if board.side_to_move == PieceColor::White { 5 } else { 2 };
+            board.en_passant = match Square::parse(fields[3]) {
+                Some(square) if square.rank() == expected_rank => Some(square),
+                _ => return Err(FenError::InvalidEnPassant(fields[3].to_string())),
+            };
+        }
+
+        if fields.len() == 6 {
+            board.halfmove_clock = fields[4]
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            board.fullmove_number = fields[5]
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| FenError::InvalidFullmoveNumber(fields[5].to_string()))?;
+        }
+
+        board.validate_fen_position()?;
+        board.zobrist = board.compute_zobrist();
+        board.history.push(board.repetition_key());
+        Ok(board)
+    }
+
+    fn validate_fen_position(&mut self) -> Result<(), FenError> {
+        for color in [PieceColor::White, PieceColor::Black] {
+            let kings = self.pieces_of(PieceType::King, color).count_ones() as usize;
+            if kings != 1 {
+                return Err(FenError::KingCount(color, kings));
+            }
+        }
+
+        let misplaced_pawns = self.pieces[PieceType::Pawn as usize] & (RANK_1 | RANK_8);
+        if let Some(square) = bitboard_squares(misplaced_pawns).next() {
+            return Err(FenError::PawnOnBackRank(square));
+        }
+
+        if self.is_king_in_check(self.side_to_move.opposite()) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        // Castling rights only survive if king and rook are still on their
+        // back rank, so stale rights are dropped rather than rejected
+        for (color, rank) in [(PieceColor::White, 0), (PieceColor::Black, 7)] {
+            let king = self.king_square(color).filter(|king| king.rank() == rank);
+            for kingside in [true, false] {
+                let rook = Piece { piece_type: PieceType::Rook, color };
+                let valid = match (king, self.castling_rights.rook_file(color, kingside)) {
+                    (Some(king), Some(file)) => {
+                        self.piece_at(Square::new(file, rank)) == Some(rook) && (file > king.file()) == kingside
+                    }
+                    _ => false,
+                };
+                if !valid {
+                    *self.castling_rights.rook_file_mut(color, kingside) = None;
+                }
+            }
+        }
+
+        // Likewise, ignore an en passant square with no pawn that could have just moved past it
+        if let Some(square) = self.en_passant {
+            let pawn_rank = if self.side_to_move == PieceColor::White { 4 } else { 3 };
+            let pushed = Piece { piece_type: PieceType::Pawn, color: self.side_to_move.opposite() };
+            if self.piece_at(Square::new(square.file(), pawn_rank)) != Some(pushed) || self.piece_at(square).is_some() {
+                self.en_passant = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the position as FEN, using X-FEN castling rights where needed
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(Square::new(file, rank)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(piece_to_char(piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
+        }
+
+        let side = if self.side_to_move == PieceColor::White { "w" } else { "b" };
+
+        // X-FEN: KQkq, unless a right belongs to a rook other than the
+        // outermost one on its side, which is then named by its file
+        let mut castling = String::new();
+        for (color, kingside, c) in [
+            (PieceColor::White, true, 'K'),
+            (PieceColor::White, false, 'Q'),
+            (PieceColor::Black, true, 'k'),
+            (PieceColor::Black, false, 'q'),
+        ] {
+            if let Some(file) = self.castling_rights.rook_file(color, kingside) {
+                if self.outermost_rook_file(color, kingside) == Some(file) {
+                    castling.push(c);
+                } else {
+                    let file = (b'a' + file as u8) as char;
+                    castling.push(if color == PieceColor::White { file.to_ascii_uppercase() } else { file });
+                }
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = match self.en_passant {
+            Some(square) => square.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            placement, side, castling, en_passant, self.halfmove_clock, self.fullmove_number
+        )
+    }
+
+    /// Reads a move in Standard Algebraic Notation, such as "Nf3", "exd5",
+    /// "Rad1", "e8=Q+" or "O-O", and finds the legal move it stands for.
+    /// A pawn reaching the last rank without "=Q" has no promotion piece yet
+    pub fn parse_san(&self, san: &str) -> Result<Move, MoveError> {
+        // Check and annotation marks don't change which move is meant
+        let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        match san {
+            "O-O" | "0-0" => return self.san_castle(true),
+            "O-O-O" | "0-0-0" => return self.san_castle(false),
+            _ => {}
+        }
+        if !san.is_ascii() || san.len() < 2 {
+            return Err(MoveError::BadSyntax);
+        }
+
+        // Promotion, written "e8=Q" or "e8Q"
+        let (rest, promotion) = match san.rsplit_once('=') {
+            Some((rest, piece)) => (rest, Some(parse_promotion(piece).ok_or(MoveError::BadSyntax)?)),
+            None if san.ends_with(['Q', 'R', 'B', 'N']) && san.len() > 2 => {
+                (&san[..san.len() - 1], parse_promotion(&san[san.len() - 1..]))
+            }
+            None => (san, None),
+        };
+
+        let (piece_type, rest) = match rest.chars().next() {
+            Some('N') => (PieceType::Knight, &rest[1..]),
+            Some('B') => (PieceType::Bishop, &rest[1..]),
+            Some('R') => (PieceType::Rook, &rest[1..]),
+            Some('Q') => (PieceType::Queen, &rest[1..]),
+            Some('K') => (PieceType::King, &rest[1..]),
+            _ => (PieceType::Pawn, rest),
+        };
+        if rest.len() < 2 {
+            return Err(MoveError::BadSyntax);
+        }
+        let to = Square::parse(&rest[rest.len() - 2..]).ok_or(MoveError::BadSyntax)?;
+
+        // What is left names the starting file and/or rank, then "x" for a capture
+        let mut hint = &rest[..rest.len() - 2];
+        let capture = hint.ends_with('x');
+        hint = hint.strip_suffix(['x', '-']).unwrap_or(hint);
+        let (mut from_file, mut from_rank) = (None, None);
+        for c in hint.chars() {
+            match c {
+                'a'..='h' if from_file.is_none() && from_rank.is_none() => from_file = Some(c as usize - 'a' as usize),
+                '1'..='8' if from_rank.is_none() => from_rank = Some(c as usize - '1' as usize),
+                _ => return Err(MoveError::BadSyntax),
+            }
+        }
+        // A pawn that doesn't capture stays on its file
+        if piece_type == PieceType::Pawn && !capture && from_file.is_none() {
+            from_file = Some(to.file());
+        }
+
+        let us = self.side_to_move;
+        let from_matches = |from: Square| {
+            self.piece_at(from) == Some(Piece { piece_type, color: us })
+                && from_file.is_none_or(|file| from.file() == file)
+                && from_rank.is_none_or(|rank| from.rank() == rank)
+        };
+        let candidates: Vec<Move> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| mv.kind != MoveKind::Castle && mv.to == to && from_matches(mv.from))
+            .collect();
+
+        // Without "=Q" the queen promotion stands for all four, so the
+        // player can still be asked which piece they want
+        let matching: Vec<Move> = candidates
+            .iter()
+            .filter(|mv| mv.promotion == promotion || (promotion.is_none() && mv.promotion == Some(PieceType::Queen)))
+            .map(|mv| Move { promotion, ..*mv })
+            .collect();
+
+        match matching.as_slice() {
+            [mv] if capture && !mv.is_capture() => Err(MoveError::NothingToCapture(to)),
+            [mv] => Ok(*mv),
+            [_, _, ..] => Err(MoveError::AmbiguousMove(piece_type, to)),
+            [] if !candidates.is_empty() => Err(MoveError::InvalidPromotion),
+            [] => {
+                // With a single piece that could be meant, say what is wrong with its move
+                let pieces = self.pieces[piece_type as usize] & self.colors[us as usize];
+                let mut froms = bitboard_squares(pieces).filter(|&from| from_matches(from));
+                match (froms.next(), froms.next()) {
+                    (Some(from), None) => {
+                        let mv = self.create_move(from, to, promotion);
+                        self.check_move(mv).map(|_| mv)
+                    }
+                    _ => Err(MoveError::NoMatchingMove(piece_type, to)),
+                }
+            }
+        }
+    }
+
+    fn san_castle(&self, kingside: bool) -> Result<Move, MoveError> {
+        let us = self.side_to_move;
+        match (self.king_square(us), self.castling_rook(us, kingside)) {
+            (Some(king), Some(rook)) => {
+                let mv = Move { from: king, to: rook, promotion: None, kind: MoveKind::Castle };
+                self.check_move(mv).map(|_| mv)
+            }
+            _ => Err(MoveError::CastlingRightsLost),
+        }
+    }
+
+    /// Reads a move typed by a player, either in SAN or as squares like "e2 e4"
+    pub fn parse_move(&self, input: &str) -> Result<Move, MoveError> {
+        match parse_move_input(input) {
+            Ok((from, to, promotion)) => Ok(self.create_move(from, to, promotion)),
+            Err(_) => self.parse_san(input),
+        }
+    }
+}
+
+impl Move {
+    /// The move in Standard Algebraic Notation, such as "Nbd2", "exd5+",
+    /// "e8=Q#" or "O-O", for the position it is played from
+    pub fn to_san(&self, board: &Board) -> String {
+        let mv = board.normalize_move(*self);
+        let mut san = String::new();
+        let piece_type = board.piece_at(self.from).map_or(PieceType::Pawn, |piece| piece.piece_type);
+
+        if self.kind == MoveKind::Castle {
+            san.push_str(if self.to.file() > self.from.file() { "O-O" } else { "O-O-O" });
+        } else if piece_type == PieceType::Pawn {
+            if self.is_capture() {
+                san.push((b'a' + self.from.file() as u8) as char);
+                san.push('x');
+            }
+            san.push_str(&self.to.to_string());
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.push(piece_to_char(Piece { piece_type: promotion, color: PieceColor::White }));
+            }
+        } else {
+            san.push(piece_to_char(Piece { piece_type, color: PieceColor::White }));
+
+            // Name the file, else the rank, else both, when another piece
+            // of the same type can go to the same square
+            let others: Vec<Square> = board
+                .legal_moves()
+                .into_iter()
+                .filter(|mv| mv.kind != MoveKind::Castle && mv.to == self.to && mv.from != self.from)
+                .filter(|mv| board.piece_at(mv.from).is_some_and(|piece| piece.piece_type == piece_type))
+                .map(|mv| mv.from)
+                .collect();
+            if !others.is_empty() {
+                let file = (b'a' + self.from.file() as u8) as char;
+                let rank = (b'1' + self.from.rank() as u8) as char;
+                if others.iter().all(|other| other.file() != self.from.file()) {
+                    san.push(file);
+                } else if others.iter().all(|other| other.rank() != self.from.rank()) {
+                    san.push(rank);
+                } else {
+                    san.push(file);
+                    san.push(rank);
+                }
+            }
+
+            if self.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&self.to.to_string());
+        }
+
+        let mut after = board.clone();
+        after.play_move(mv);
+        if after.is_checkmate() {
+            san.push('#');
+        } else if after.is_king_in_check(after.side_to_move) {
+            san.push('+');
+        }
+        san
+    }
+}
+
+/// Reads a FEN piece letter, uppercase for White
+pub fn piece_from_char(c: char) -> Option<Piece> {
+    let piece_type = match c.to_ascii_lowercase() {
+        'p' => PieceType::Pawn,
+        'n' => PieceType::Knight,
+        'b' => PieceType::Bishop,
+        'r' => PieceType::Rook,
+        'q' => PieceType::Queen,
+        'k' => PieceType::King,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+    Some(Piece { piece_type, color })
+}
+
+/// The FEN letter of a piece, uppercase for White
+pub fn piece_to_char(piece: Piece) -> char {
+    let c = match piece.piece_type {
+        PieceType::Pawn => 'p',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Rook => 'r',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    if piece.color == PieceColor::White { c.to_ascii_uppercase() } else { c }
+}
+
+/// Reads a promotion piece letter: q, r, b or n
+pub fn parse_promotion(s: &str) -> Option<PieceType> {
+    match s.to_ascii_lowercase().as_str() {
+        "q" => Some(PieceType::Queen),
+        "r" => Some(PieceType::Rook),
+        "b" => Some(PieceType::Bishop),
+        "n" => Some(PieceType::Knight),
+        _ => None,
+    }
+}
+
+/// Name of a piece type for messages to players
+pub fn promotion_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::Queen => "Queen",
+        PieceType::Rook => "Rook",
+        PieceType::Bishop => "Bishop",
+        PieceType::Knight => "Knight",
+        PieceType::King => "King",
+        PieceType::Pawn => "Pawn",
+    }
+}
+
+/// Parses a move typed as "e2 e4", "e2e4", "e7 e8 n" or "e7e8n"
+pub fn parse_move_input(input: &str) -> Result<(Square, Square, Option<PieceType>), MoveError> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    let (from, to, promotion) = match parts.as_slice() {
+        [from, to] => (*from, *to, None),
+        [from, to, promotion] => (*from, *to, Some(*promotion)),
+        [joined] if joined.is_ascii() && (joined.len() == 4 || joined.len() == 5) => {
+            let promotion = if joined.len() == 5 { Some(&joined[4..]) } else { None };
+            (&joined[0..2], &joined[2..4], promotion)
+        }
+        _ => return Err(MoveError::BadSyntax),
+    };
+
+    let promotion = match promotion {
+        Some(p) => Some(parse_promotion(p).ok_or(MoveError::BadSyntax)?),
+        None => None,
+    };
+    match (Square::parse(from), Square::parse(to)) {
+        (Some(from), Some(to)) => Ok((from, to, promotion)),
+        _ => Err(MoveError::BadSyntax),
+    }
+}
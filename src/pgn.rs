@@ -0,0 +1,376 @@
+//! Portable Game Notation, for saving games and reading them back.
+
+use crate::board::{Board, MoveError, PieceColor};
+use crate::game::{Game, GameResult, Termination};
+use crate::notation::FenError;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The Seven Tag Roster apart from the result, which comes from the game
+#[derive(Clone, PartialEq, Debug)]
+pub struct PgnTags {
+    pub event: String,
+    pub site: String,
+    /// "YYYY.MM.DD", with "??" for unknown parts
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl Default for PgnTags {
+    // Unknown tags are "?", and the date is today's
+    fn default() -> Self {
+        PgnTags {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: today(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+        }
+    }
+}
+
+// Today's date in UTC as "YYYY.MM.DD"
+fn today() -> String {
+    let secs = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(_) => return "????.??.??".to_string(),
+    };
+
+    // Civil date from the day count, after Howard Hinnant's civil_from_days
+    let days = secs.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// Quotes a tag value, escaping backslashes and quotes
+fn tag(name: &str, value: &str) -> String {
+    format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Castling with a rook that is not in the corner, or a king off the e-file,
+// only happens in Chess960
+fn is_chess960(board: &Board) -> bool {
+    let rights = board.castling_rights();
+    [PieceColor::White, PieceColor::Black].into_iter().any(|color| {
+        let king_on_e = board.king_square(color).is_some_and(|king| king.file() == 4);
+        [(true, 7), (false, 0)].into_iter().any(|(kingside, corner)| {
+            rights.rook_file(color, kingside).is_some_and(|file| file != corner || !king_on_e)
+        })
+    })
+}
+
+impl Game {
+    /// The moves so far as numbered SAN, e.g. "1. e4 e5 2. Nf3"
+    pub fn movetext(&self) -> String {
+        self.movetext_tokens().join(" ")
+    }
+
+    fn movetext_tokens(&self) -> Vec<String> {
+        let start = self.start_position();
+        let mut number = start.fullmove_number();
+        let mut color = start.side_to_move();
+        let mut tokens = Vec::new();
+        for (i, san) in self.san_moves().into_iter().enumerate() {
+            match color {
+                PieceColor::White => tokens.push(format!("{}.", number)),
+                PieceColor::Black if i == 0 => tokens.push(format!("{}...", number)),
+                PieceColor::Black => {}
+            }
+            tokens.push(san);
+            if color == PieceColor::Black {
+                number += 1;
+            }
+            color = color.opposite();
+        }
+        tokens
+    }
+
+    /// The game in PGN, finished or not, with the movetext wrapped at 80 columns
+    pub fn to_pgn(&self, tags: &PgnTags) -> String {
+        let result = self.result().map_or("*", |result| result.to_pgn());
+
+        let mut pgn = String::new();
+        pgn.push_str(&tag("Event", &tags.event));
+        pgn.push_str(&tag("Site", &tags.site));
+        pgn.push_str(&tag("Date", &tags.date));
+        pgn.push_str(&tag("Round", &tags.round));
+        pgn.push_str(&tag("White", &tags.white));
+        pgn.push_str(&tag("Black", &tags.black));
+        pgn.push_str(&tag("Result", result));
+
+        // Games not starting from the standard position carry it with them
+        let start = self.start_position();
+        let start_fen = start.to_fen();
+        if is_chess960(start) {
+            pgn.push_str(&tag("Variant", "Chess960"));
+        }
+        if start_fen != Board::new().to_fen() {
+            pgn.push_str(&tag("SetUp", "1"));
+            pgn.push_str(&tag("FEN", &start_fen));
+        }
+
+        let termination = match self.termination() {
+            None => "unterminated",
+            Some(Termination::Timeout) => "time forfeit",
+            Some(_) => "normal",
+        };
+        pgn.push_str(&tag("Termination", termination));
+        let time_control = match self.time_control() {
+            Some(tc) => format!("{}+{}", tc.initial.as_secs(), tc.increment.as_secs()),
+            None => "-".to_string(),
+        };
+        pgn.push_str(&tag("TimeControl", &time_control));
+        pgn.push('\n');
+
+        // How the game ended goes in a comment just before the result
+        let mut tokens = self.movetext_tokens();
+        if let Some((result, termination)) = self.result().zip(self.termination()) {
+            let winner = match result {
+                GameResult::WhiteWins => "White wins",
+                GameResult::BlackWins => "Black wins",
+                GameResult::Draw => "Draw",
+            };
+            tokens.push(format!("{{{} by {}}}", winner, termination.description()));
+        }
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// Why a PGN file could not be read; games are numbered from 1
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    BadTag(usize, String),
+    UnterminatedComment(usize),
+    UnbalancedVariation(usize),
+    InvalidFen(usize, FenError),
+    IllegalMove { game: usize, ply: usize, san: String, error: MoveError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::BadTag(game, tag) => write!(f, "game {}: tag pairs look like [Name \"value\"], found '{}'", game, tag),
+            PgnError::UnterminatedComment(game) => write!(f, "game {}: a comment opened with '{{' is never closed", game),
+            PgnError::UnbalancedVariation(game) => write!(f, "game {}: the '(' and ')' around variations don't match", game),
+            PgnError::InvalidFen(game, err) => write!(f, "game {}: invalid FEN tag: {}", game, err),
+            PgnError::IllegalMove { game, ply, san, error } => {
+                let number = ply.div_ceil(2);
+                let dots = if ply % 2 == 1 { "." } else { "..." };
+                write!(f, "game {}: move {}{} {} can't be played: {}", game, number, dots, san, error)
+            }
+        }
+    }
+}
+
+/// A game read from PGN, with its moves checked against the rules
+#[derive(Clone)]
+pub struct PgnGame {
+    /// The tag pairs in the order they were written
+    pub tags: Vec<(String, String)>,
+    pub game: Game,
+    /// The result token after the moves; None for "*" or a missing one
+    pub result: Option<GameResult>,
+}
+
+impl PgnGame {
+    /// The value of a tag, such as "White" or "Event"
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+}
+
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Result(Option<GameResult>),
+    VariationStart,
+    VariationEnd,
+}
+
+// Splits PGN text into tags, moves, results and variation brackets,
+// dropping comments, NAGs, move numbers and escaped lines. `game` counts
+// the result tokens seen so far, so errors name the right game
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut game = 1;
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        let at_line_start = line_start;
+        line_start = c == '\n';
+        match c {
+            c if c.is_whitespace() => {}
+            // A '%' in the first column escapes the whole line
+            ';' | '%' if c == ';' || at_line_start => {
+                chars.by_ref().find(|&c| c == '\n');
+                line_start = true;
+            }
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err(PgnError::UnterminatedComment(game));
+                }
+            }
+            '$' => {
+                while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '[' => {
+                let mut tag = String::new();
+                let mut in_string = false;
+                loop {
+                    match chars.next() {
+                        Some('\\') if in_string => tag.extend(chars.next()),
+                        Some('"') => {
+                            in_string = !in_string;
+                            tag.push('"');
+                        }
+                        Some(']') if !in_string => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(PgnError::BadTag(game, format!("[{}", tag))),
+                    }
+                }
+                let (name, value) = tag.trim().split_once(char::is_whitespace).unwrap_or((tag.trim(), ""));
+                let value = value.trim();
+                if name.is_empty() || value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                    return Err(PgnError::BadTag(game, format!("[{}]", tag)));
+                }
+                tokens.push(Token::Tag(name.to_string(), value[1..value.len() - 1].to_string()));
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"{}()[];$".contains(*c)) {
+                    word.push(c);
+                }
+                let result = match word.as_str() {
+                    "1-0" => Some(Some(GameResult::WhiteWins)),
+                    "0-1" => Some(Some(GameResult::BlackWins)),
+                    "1/2-1/2" => Some(Some(GameResult::Draw)),
+                    "*" => Some(None),
+                    _ => None,
+                };
+                if let Some(result) = result {
+                    tokens.push(Token::Result(result));
+                    game += 1;
+                    continue;
+                }
+
+                // Move numbers come as "12." or "12...", or glued to the move as in "12.e4"
+                let san = if word.starts_with("0-0") {
+                    word.as_str()
+                } else {
+                    word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+                };
+                if !san.is_empty() {
+                    tokens.push(Token::Move(san.to_string()));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// A game whose tags and moves are still being read
+#[derive(Default)]
+struct PendingGame {
+    tags: Vec<(String, String)>,
+    game: Option<Game>,
+    has_movetext: bool,
+}
+
+impl PendingGame {
+    fn game(&mut self, number: usize) -> Result<&mut Game, PgnError> {
+        if self.game.is_none() {
+            let board = match self.tags.iter().find(|(tag, _)| tag == "FEN") {
+                Some((_, fen)) => Board::from_fen(fen).map_err(|err| PgnError::InvalidFen(number, err))?,
+                None => Board::new(),
+            };
+            self.game = Some(Game::new(board));
+        }
+        Ok(self.game.as_mut().unwrap())
+    }
+
+    fn finish(mut self, number: usize, result: Option<GameResult>) -> Result<PgnGame, PgnError> {
+        let game = self.game(number)?.clone();
+        Ok(PgnGame { tags: self.tags, game, result })
+    }
+}
+
+/// Reads every game in a PGN file. Moves inside variations are skipped,
+/// and the main line is checked move by move against the rules
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut pending = PendingGame::default();
+    let mut depth = 0;
+
+    for token in tokenize(text)? {
+        let number = games.len() + 1;
+        match token {
+            // Tags after moves start the next game, even without a result
+            Token::Tag(name, value) => {
+                if pending.has_movetext {
+                    games.push(std::mem::take(&mut pending).finish(number, None)?);
+                }
+                pending.tags.push((name, value));
+            }
+            Token::VariationStart => {
+                pending.has_movetext = true;
+                depth += 1;
+            }
+            Token::VariationEnd if depth == 0 => return Err(PgnError::UnbalancedVariation(number)),
+            Token::VariationEnd => depth -= 1,
+            Token::Move(_) | Token::Result(_) if depth > 0 => {}
+            Token::Move(san) => {
+                pending.has_movetext = true;
+                let game = pending.game(number)?;
+                let ply = game.moves().len() + 1;
+                let ply = match game.start_position().side_to_move() {
+                    PieceColor::White => ply,
+                    PieceColor::Black => ply + 1,
+                } + 2 * (game.start_position().fullmove_number() as usize - 1);
+                let illegal = |error| PgnError::IllegalMove { game: number, ply, san: san.clone(), error };
+                let mv = game.board().parse_san(&san).map_err(illegal)?;
+                game.make_move(mv).map_err(illegal)?;
+            }
+            Token::Result(result) => {
+                games.push(std::mem::take(&mut pending).finish(number, result)?);
+            }
+        }
+    }
+
+    if depth != 0 {
+        return Err(PgnError::UnbalancedVariation(games.len() + 1));
+    }
+    if pending.has_movetext || !pending.tags.is_empty() {
+        let number = games.len() + 1;
+        games.push(pending.finish(number, None)?);
+    }
+    Ok(games)
+}
@@ -0,0 +1,207 @@
+//! Move search for the computer player.
+
+use crate::board::{Board, Move, PieceColor};
+use crate::eval;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// When a search has to stop; a search without any limit runs until it is
+/// stopped, or to the deepest depth it allows
+#[derive(Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    /// Set from another thread to stop the search early
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+/// What a search found after completing a depth
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SearchInfo {
+    pub depth: i32,
+    /// From the point of view of the side to move at the root, in
+    /// centipawns or as a mate score (see [`SearchInfo::mate_in`])
+    pub score: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub best_move: Move,
+}
+
+impl SearchInfo {
+    /// The number of moves to a forced mate if the score is one, negative
+    /// when the side to move is the one getting mated
+    pub fn mate_in(&self) -> Option<i32> {
+        let plies = ChessAI::MATE_SCORE - self.score.abs();
+        if plies > ChessAI::DEPTH_CAP {
+            return None;
+        }
+        let moves = (plies + 1) / 2;
+        Some(if self.score > 0 { moves } else { -moves })
+    }
+}
+
+// Node count and limits of a running search
+struct SearchState<'a> {
+    limits: &'a SearchLimits,
+    start: Instant,
+    nodes: u64,
+    // Plies from the root, so nearer mates score higher
+    ply: i32,
+    aborted: bool,
+}
+
+impl SearchState<'_> {
+    // Counts a node and tells whether the search must unwind
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.limits.nodes.is_some_and(|nodes| self.nodes > nodes) {
+            self.aborted = true;
+        }
+        // Clock and stop flag are only looked at every so often
+        if self.nodes.is_multiple_of(1024) {
+            let out_of_time = self.limits.movetime.is_some_and(|movetime| self.start.elapsed() >= movetime);
+            let stopped = self.limits.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed));
+            self.aborted |= out_of_time || stopped;
+        }
+        self.aborted
+    }
+}
+
+/// Computer player for one side, searching with minimax and alpha-beta pruning
+pub struct ChessAI {
+    color: PieceColor,
+}
+
+impl ChessAI {
+    /// Search depth in plies
+    pub const MAX_DEPTH: i32 = 3;  // Increase for stronger but slower AI
+
+    /// Deepest depth a search without a depth limit goes to
+    pub const DEPTH_CAP: i32 = 64;
+
+    /// Score of checkmating at the root; a mate found further away scores
+    /// one less for every ply
+    pub const MATE_SCORE: i32 = 100_000;
+
+    /// An AI playing the given color
+    pub fn new(color: PieceColor) -> Self {
+        ChessAI { color }
+    }
+
+    /// Scores the position from the point of view of this AI's color
+    pub fn evaluate_position(&self, board: &Board) -> i32 {
+        eval::evaluate(board, self.color)
+    }
+
+    fn get_all_possible_moves(&self, board: &Board) -> Vec<Move> {
+        board.legal_moves()
+    }
+
+    fn minimax(&self, board: &mut Board, depth: i32, alpha: i32, beta: i32, maximizing: bool, state: &mut SearchState) -> i32 {
+        if state.visit() {
+            return 0;
+        }
+        if board.is_insufficient_material() || board.claimable_draw().is_some() {
+            return 0;
+        }
+
+        if depth == 0 {
+            return self.evaluate_position(board);
+        }
+
+        let moves = self.get_all_possible_moves(board);
+        if moves.is_empty() {
+            if !board.is_king_in_check(board.side_to_move()) {
+                return 0;
+            }
+            let mate = ChessAI::MATE_SCORE - state.ply;
+            return if maximizing { -mate } else { mate };
+        }
+
+        if maximizing {
+            let mut max_eval = i32::MIN;
+            for mv in moves {
+                let undo = board.play_move(mv);
+                state.ply += 1;
+                let eval = self.minimax(board, depth - 1, alpha, beta, false, state);
+                state.ply -= 1;
+                board.unmake_move(undo);
+                max_eval = max_eval.max(eval);
+                if max_eval >= beta {
+                    break;
+                }
+            }
+            max_eval
+        } else {
+            let mut min_eval = i32::MAX;
+            for mv in moves {
+                let undo = board.play_move(mv);
+                state.ply += 1;
+                let eval = self.minimax(board, depth - 1, alpha, beta, true, state);
+                state.ply -= 1;
+                board.unmake_move(undo);
+                min_eval = min_eval.min(eval);
+                if min_eval <= alpha {
+                    break;
+                }
+            }
+            min_eval
+        }
+    }
+
+    /// The move the AI plays in this position, or None if it has none
+    pub fn make_move(&self, board: &Board) -> Option<Move> {
+        let limits = SearchLimits { depth: Some(ChessAI::MAX_DEPTH), ..SearchLimits::default() };
+        self.search(board, &limits, |_| {})
+    }
+
+    /// Searches one depth deeper at a time until a limit is reached,
+    /// reporting each completed depth. A depth cut short by a limit is
+    /// thrown away, but the first one always gives a move
+    pub fn search(&self, board: &Board, limits: &SearchLimits, mut report: impl FnMut(&SearchInfo)) -> Option<Move> {
+        // Scores are always for the side to move, whichever color this AI was made for
+        let ai = ChessAI::new(board.side_to_move());
+
+        // Search on a single copy, making and unmaking moves in place
+        let mut board = board.clone();
+        let moves = ai.get_all_possible_moves(&board);
+        let mut best_move = *moves.first()?;
+        let mut state = SearchState { limits, start: Instant::now(), nodes: 0, ply: 1, aborted: false };
+
+        for depth in 1..=limits.depth.unwrap_or(ChessAI::DEPTH_CAP) {
+            let mut depth_best = None;
+            let mut best_eval = i32::MIN;
+
+            for &mv in &moves {
+                let undo = board.play_move(mv);
+                let eval = ai.minimax(&mut board, depth - 1, i32::MIN, i32::MAX, false, &mut state);
+                board.unmake_move(undo);
+                if state.aborted {
+                    break;
+                }
+                if eval > best_eval {
+                    best_eval = eval;
+                    depth_best = Some(mv);
+                }
+            }
+
+            if state.aborted {
+                break;
+            }
+            if let Some(mv) = depth_best {
+                best_move = mv;
+            }
+            report(&SearchInfo {
+                depth,
+                score: best_eval,
+                nodes: state.nodes,
+                elapsed: state.start.elapsed(),
+                best_move,
+            });
+        }
+
+        Some(best_move)
+    }
+}
@@ -0,0 +1,283 @@
+//! The Universal Chess Interface, so GUIs and match runners can play
+//! against [`ChessAI`].
+
+use crate::board::{Board, Move, MoveKind, PieceColor};
+use crate::notation::parse_move_input;
+use crate::search::{ChessAI, SearchInfo, SearchLimits};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+// Kept back from the clock for communication delays
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+// Moves the remaining time is shared over when the GUI doesn't say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// The parameters of a `go` command
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct GoParams {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub infinite: bool,
+}
+
+impl GoParams {
+    /// Reads the words after `go`; unknown or malformed ones are skipped
+    pub fn parse(args: &[&str]) -> GoParams {
+        let mut params = GoParams::default();
+        let mut args = args.iter();
+        while let Some(&name) = args.next() {
+            let mut value = || args.next().and_then(|value| value.parse::<u64>().ok());
+            match name {
+                "depth" => params.depth = value().map(|depth| depth as i32),
+                "nodes" => params.nodes = value(),
+                "movetime" => params.movetime = value().map(Duration::from_millis),
+                "wtime" => params.wtime = value().map(Duration::from_millis),
+                "btime" => params.btime = value().map(Duration::from_millis),
+                "winc" => params.winc = value().map(Duration::from_millis),
+                "binc" => params.binc = value().map(Duration::from_millis),
+                "movestogo" => params.movestogo = value().map(|moves| moves as u32),
+                "infinite" => params.infinite = true,
+                _ => {}
+            }
+        }
+        params
+    }
+
+    /// The limits of the search for `color`. Without a clock or any other
+    /// limit the AI searches to its usual depth, unless told to go on
+    /// until stopped
+    pub fn limits(&self, color: PieceColor) -> SearchLimits {
+        let (time, increment) = match color {
+            PieceColor::White => (self.wtime, self.winc),
+            PieceColor::Black => (self.btime, self.binc),
+        };
+
+        // An even share of the remaining time, plus most of the increment
+        let movetime = self.movetime.or_else(|| {
+            time.map(|time| {
+                let increment = increment.unwrap_or_default();
+                let share = time / self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1) + increment * 3 / 4;
+                share.min(time.saturating_sub(MOVE_OVERHEAD)).max(Duration::from_millis(1))
+            })
+        });
+
+        let unlimited = self.infinite || movetime.is_some() || self.nodes.is_some();
+        let depth = self.depth.or(if unlimited { None } else { Some(ChessAI::MAX_DEPTH) });
+        SearchLimits { depth, nodes: self.nodes, movetime: if self.infinite { None } else { movetime }, stop: None }
+    }
+}
+
+// A search started by `go`, which prints its best move when it ends
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    // Wakes a finished `go infinite` search, which holds its move until `stop`
+    stopped: Arc<Notify>,
+    infinite: bool,
+    task: JoinHandle<()>,
+}
+
+/// The state of a UCI session: the position set up by the GUI and the
+/// search running on it, if any
+pub struct Uci {
+    board: Board,
+    chess960: bool,
+    search: Option<RunningSearch>,
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Uci::new()
+    }
+}
+
+impl Uci {
+    pub fn new() -> Uci {
+        Uci { board: Board::new(), chess960: false, search: None }
+    }
+
+    /// The position the next search starts from
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Handles one command line from the GUI, returning false on `quit`.
+    /// A `go` returns at once with the search left running; commands that
+    /// end it wait for its `bestmove` to be printed
+    pub async fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["uci", ..] => {
+                println!("id name RustChess");
+                println!("id author RustChess developers");
+                println!("option name UCI_Chess960 type check default false");
+                println!("uciok");
+            }
+            ["isready", ..] => println!("readyok"),
+            ["ucinewgame", ..] => {
+                self.stop().await;
+                self.board = Board::new();
+            }
+            ["setoption", args @ ..] => self.set_option(args),
+            ["position", args @ ..] => {
+                self.stop().await;
+                self.set_position(args);
+            }
+            ["go", args @ ..] => self.go(GoParams::parse(args)).await,
+            ["stop", ..] => self.stop().await,
+            ["quit", ..] => {
+                self.stop().await;
+                return false;
+            }
+            // "debug", "register" and "ponderhit" need nothing from this engine
+            _ => {}
+        }
+        true
+    }
+
+    // `setoption name <id> [value <x>]`, where the name may have spaces
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args.iter().position(|&word| word == "value").unwrap_or(args.len());
+        let name = args.get(1..value_at).unwrap_or_default().join(" ");
+        let value = args.get(value_at + 1..).unwrap_or_default().join(" ");
+        if name.eq_ignore_ascii_case("UCI_Chess960") {
+            self.chess960 = value == "true";
+        }
+    }
+
+    // `position startpos|fen <fen> [moves <move>...]`; moves stop at the
+    // first one that is not legal
+    fn set_position(&mut self, args: &[&str]) {
+        let moves_at = args.iter().position(|&word| word == "moves").unwrap_or(args.len());
+        let board = match &args[..moves_at] {
+            ["startpos"] => Board::new(),
+            ["fen", fen @ ..] => match Board::from_fen(&fen.join(" ")) {
+                Ok(board) => board,
+                Err(err) => {
+                    println!("info string invalid FEN: {}", err);
+                    return;
+                }
+            },
+            _ => {
+                println!("info string expected 'position startpos' or 'position fen <fen>'");
+                return;
+            }
+        };
+
+        self.board = board;
+        for &text in args.get(moves_at + 1..).unwrap_or_default() {
+            let played = parse_move_input(text).and_then(|(from, to, promotion)| {
+                self.board.make_move(self.board.create_move(from, to, promotion))
+            });
+            if let Err(err) = played {
+                println!("info string illegal move {}: {}", text, err);
+                break;
+            }
+        }
+    }
+
+    async fn go(&mut self, params: GoParams) {
+        self.stop().await;
+
+        let board = self.board.clone();
+        let chess960 = self.chess960;
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut limits = params.limits(board.side_to_move());
+        limits.stop = Some(stop.clone());
+
+        let stopped = Arc::new(Notify::new());
+        let task_stopped = stopped.clone();
+        let task = tokio::spawn(async move {
+            let best = tokio::task::spawn_blocking(move || {
+                let ai = ChessAI::new(board.side_to_move());
+                ai.search(&board, &limits, |info| println!("{}", info_line(info, chess960)))
+            })
+            .await
+            .ok()
+            .flatten();
+
+            // After `go infinite` the best move waits for `stop`
+            if params.infinite {
+                task_stopped.notified().await;
+            }
+            match best {
+                Some(mv) => println!("bestmove {}", uci_move(mv, chess960)),
+                None => println!("bestmove 0000"),
+            }
+        });
+        self.search = Some(RunningSearch { stop, stopped, infinite: params.infinite, task });
+    }
+
+    // Stops a running search; its best move is printed before this returns
+    async fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.stop.store(true, Ordering::Relaxed);
+            search.stopped.notify_one();
+        }
+        self.wait().await;
+    }
+
+    /// Waits for a running search to end by itself, only stopping one that
+    /// would go on until stopped
+    pub async fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            if search.infinite {
+                search.stop.store(true, Ordering::Relaxed);
+                search.stopped.notify_one();
+            }
+            let _ = search.task.await;
+        }
+    }
+}
+
+/// A move as UCI writes it. Castling is the king's move, or the king
+/// taking its own rook in Chess960 mode
+pub fn uci_move(mv: Move, chess960: bool) -> String {
+    if mv.kind == MoveKind::Castle && chess960 {
+        format!("{}{}", mv.from, mv.to)
+    } else {
+        mv.to_string()
+    }
+}
+
+// Mates are given in moves, e.g. `score mate -2` when the engine is mated in two
+fn info_line(info: &SearchInfo, chess960: bool) -> String {
+    let millis = info.elapsed.as_millis().max(1) as u64;
+    let score = match info.mate_in() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score),
+    };
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.nodes * 1000 / millis,
+        info.elapsed.as_millis(),
+        uci_move(info.best_move, chess960)
+    )
+}
+
+/// Runs a UCI session on stdin and stdout until `quit` or the end of input.
+/// Commands keep being read while a search runs, so `stop` and `isready`
+/// are answered at once
+pub async fn run() {
+    let mut uci = Uci::new();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if !uci.handle(&line).await {
+            return;
+        }
+    }
+    uci.wait().await;
+}
@@ -0,0 +1,283 @@
+//! The Chess Engine Communication Protocol spoken by XBoard, WinBoard and
+//! older tools, backed by the same search as UCI.
+
+use crate::board::{Board, Move, MoveKind, PieceColor};
+use crate::game::{Game, GameResult, Termination};
+use crate::search::ChessAI;
+use crate::uci::GoParams;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// The state of an XBoard session: the game so far, which side the engine
+/// plays and the time it has for its moves
+pub struct Xboard {
+    game: Game,
+    // None in force mode, where the engine only records moves
+    engine_color: Option<PieceColor>,
+    chess960: bool,
+    post: bool,
+    depth: Option<i32>,
+    // Fixed time per move from `st`
+    move_time: Option<Duration>,
+    // Moves per time control from `level`, 0 for the whole game
+    level_moves: u32,
+    increment: Duration,
+    engine_time: Option<Duration>,
+    // Bumped for every search so a stale result is recognized
+    search_id: u64,
+    stop: Option<Arc<AtomicBool>>,
+    results: UnboundedSender<(u64, Option<Move>)>,
+}
+
+impl Xboard {
+    /// A session whose searches send their moves on `results`, together with
+    /// the search id that [`search_done`](Xboard::search_done) expects back
+    pub fn new(results: UnboundedSender<(u64, Option<Move>)>) -> Xboard {
+        Xboard {
+            game: Game::new(Board::new()),
+            engine_color: Some(PieceColor::Black),
+            chess960: false,
+            post: false,
+            depth: None,
+            move_time: None,
+            level_moves: 0,
+            increment: Duration::ZERO,
+            engine_time: None,
+            search_id: 0,
+            stop: None,
+            results,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Handles one command from XBoard, returning false on `quit`. The
+    /// engine's moves arrive later on the results channel; thinking happens
+    /// on a tokio blocking thread, so a runtime must be running
+    pub fn handle(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["protover", ..] => {
+                println!(
+                    "feature myname=\"RustChess\" setboard=1 usermove=1 ping=1 playother=1 san=0 \
+                     colors=0 sigint=0 sigterm=0 variants=\"normal,fischerandom\" done=1"
+                );
+            }
+            ["new"] => {
+                self.stop();
+                self.game = Game::new(Board::new());
+                self.engine_color = Some(PieceColor::Black);
+                self.chess960 = false;
+                self.depth = None;
+                self.engine_time = None;
+            }
+            ["variant", variant] => self.chess960 = *variant == "fischerandom",
+            ["force"] => {
+                self.stop();
+                self.engine_color = None;
+            }
+            ["go"] => {
+                self.stop();
+                self.engine_color = Some(self.game.board().side_to_move());
+                self.think();
+            }
+            ["playother"] => {
+                self.stop();
+                self.engine_color = Some(self.game.board().side_to_move().opposite());
+            }
+            ["usermove", text] => self.user_move(text),
+            ["level", moves, _base, increment] => self.set_level(moves, increment),
+            ["st", seconds] => {
+                if let Some(move_time) = parse_seconds(seconds) {
+                    self.move_time = Some(move_time);
+                }
+            }
+            ["sd", depth] => self.depth = depth.parse().ok(),
+            ["time", centis] => self.engine_time = centis.parse::<u64>().ok().map(|centis| Duration::from_millis(centis * 10)),
+            ["undo"] => {
+                self.stop();
+                self.game.take_back();
+            }
+            ["remove"] => {
+                self.stop();
+                self.game.take_back();
+                self.game.take_back();
+            }
+            ["setboard", fen @ ..] => {
+                self.stop();
+                match Board::from_fen(&fen.join(" ")) {
+                    Ok(board) => self.game = Game::new(board),
+                    Err(err) => println!("tellusererror Illegal position: {}", err),
+                }
+            }
+            ["post"] => self.post = true,
+            ["nopost"] => self.post = false,
+            ["ping", n] => println!("pong {}", n),
+            // Move now
+            ["?"] => {
+                if let Some(stop) = &self.stop {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+            ["result", ..] => {
+                self.stop();
+                self.engine_color = None;
+            }
+            ["quit"] => {
+                self.stop();
+                return false;
+            }
+            // "xboard", "accepted", "otim", "hard", "easy", "draw" and the
+            // like need nothing from this engine
+            _ => {}
+        }
+        true
+    }
+
+    // `level MPS BASE INC`; the base time is not needed, as the remaining
+    // time comes with each `time` command
+    fn set_level(&mut self, moves: &str, increment: &str) {
+        self.level_moves = moves.parse().unwrap_or(0);
+        self.increment = parse_seconds(increment).unwrap_or_default();
+        self.move_time = None;
+    }
+
+    fn user_move(&mut self, text: &str) {
+        self.stop();
+        let played = self.game.board().parse_move(text).and_then(|mv| self.game.make_move(mv));
+        match played {
+            Ok(()) => {
+                if !self.report_result() {
+                    self.think();
+                }
+            }
+            Err(err) => println!("Illegal move ({}): {}", err, text),
+        }
+    }
+
+    // Starts a search if it is the engine's turn in a game still going on
+    fn think(&mut self) {
+        let board = self.game.board().clone();
+        let color = board.side_to_move();
+        if self.engine_color != Some(color) || self.game.is_over() {
+            return;
+        }
+
+        // The clock is shared like a UCI `go`, over the moves left until the next time control
+        let played = board.fullmove_number() - self.game.start_position().fullmove_number();
+        let (time, increment) = (self.engine_time, Some(self.increment));
+        let params = GoParams {
+            depth: self.depth,
+            movetime: self.move_time,
+            wtime: time,
+            btime: time,
+            winc: increment,
+            binc: increment,
+            movestogo: (self.level_moves > 0).then(|| self.level_moves - played % self.level_moves),
+            ..GoParams::default()
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut limits = params.limits(color);
+        limits.stop = Some(stop.clone());
+        self.stop = Some(stop);
+
+        self.search_id += 1;
+        let id = self.search_id;
+        let post = self.post;
+        let results = self.results.clone();
+        tokio::task::spawn_blocking(move || {
+            // Thinking output: ply, score, time in centiseconds, nodes and the move
+            let best = ChessAI::new(color).search(&board, &limits, |info| {
+                if post {
+                    let centis = info.elapsed.as_millis() / 10;
+                    println!("{} {} {} {} {}", info.depth, info.score, centis, info.nodes, info.best_move.to_san(&board));
+                }
+            });
+            let _ = results.send((id, best));
+        });
+    }
+
+    /// Plays the move a search came up with, unless the game moved on
+    /// since it was started
+    pub fn search_done(&mut self, id: u64, best: Option<Move>) {
+        if id != self.search_id || self.stop.take().is_none() {
+            return;
+        }
+        let Some(mv) = best else {
+            return;
+        };
+
+        let text = self.move_text(mv);
+        if self.game.make_move(mv).is_ok() {
+            println!("move {}", text);
+            self.report_result();
+        }
+    }
+
+    // Castling is written O-O in Chess960, where the king's move alone could mean something else
+    fn move_text(&self, mv: Move) -> String {
+        match mv.kind {
+            MoveKind::Castle if self.chess960 => mv.to_san(self.game.board()).trim_end_matches(['+', '#']).to_string(),
+            _ => mv.to_string(),
+        }
+    }
+
+    // Tells the GUI when the game is over, e.g. "1-0 {White mates}"
+    fn report_result(&self) -> bool {
+        let (Some(result), Some(termination)) = (self.game.result(), self.game.termination()) else {
+            return false;
+        };
+        let reason = match (result, termination) {
+            (GameResult::WhiteWins, Termination::Checkmate) => "White mates".to_string(),
+            (GameResult::BlackWins, Termination::Checkmate) => "Black mates".to_string(),
+            (_, termination) => format!("Draw by {}", termination.description()),
+        };
+        println!("{} {{{}}}", result.to_pgn(), reason);
+        true
+    }
+
+    // Abandons a running search; its move is ignored when it comes in
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+// A time in seconds, None when it is negative, too large or not a number
+fn parse_seconds(text: &str) -> Option<Duration> {
+    text.parse().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+}
+
+/// Runs an XBoard session on stdin and stdout until `quit` or the end of
+/// input, reading commands while the engine thinks
+pub async fn run() {
+    let (results, mut moves) = unbounded_channel();
+    let mut xboard = Xboard::new(results);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    if !xboard.handle(&line) {
+                        return;
+                    }
+                }
+                _ => break,
+            },
+            Some((id, best)) = moves.recv() => xboard.search_done(id, best),
+        }
+    }
+
+    // Let a search still running finish its move
+    if xboard.stop.is_some() {
+        if let Some((id, best)) = moves.recv().await {
+            xboard.search_done(id, best);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use rustchess::{Board, Epd, EpdError, MoveError, PieceType, Square};
+
+const WAC_001: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+#[test]
+fn parse_operations() {
+    let epd = Epd::parse(WAC_001).unwrap();
+    assert_eq!(epd.id(), Some("WAC.001"));
+    assert_eq!(epd.best_moves().len(), 1);
+    assert_eq!(epd.best_moves()[0].to_san(&epd.board), "Qg6");
+    assert!(epd.avoid_moves().is_empty());
+    assert_eq!(epd.board.fullmove_number(), 1);
+
+    let epd = Epd::parse(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - am Ke2 Qe2; bm Bb5 Bc4; \
+         c0 \"quiet; developing\"; dm 12; hmvc 2; fmvn 3;",
+    )
+    .unwrap();
+    assert_eq!(epd.best_moves().len(), 2);
+    assert_eq!(epd.avoid_moves().len(), 2);
+    assert_eq!(epd.comment(), Some("quiet; developing"));
+    assert_eq!(epd.mate_in(), Some(12));
+    assert_eq!(epd.board.halfmove_clock(), 2);
+    assert_eq!(epd.board.fullmove_number(), 3);
+    assert_eq!(epd.operation("hmvc"), Some(&["2".to_string()][..]));
+}
+
+#[test]
+fn write_and_read_back() {
+    let mut epd = Epd::parse(WAC_001).unwrap();
+    epd.set_operation("c0", vec!["Fischer \"said\" so".to_string()]);
+    epd.set_operation("id", vec!["WAC 1".to_string()]);
+    let line = epd.to_epd();
+    assert_eq!(
+        line,
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC 1\"; c0 \"Fischer \\\"said\\\" so\";"
+    );
+
+    let again = Epd::parse(&line).unwrap();
+    assert_eq!(again.operations, epd.operations);
+    assert_eq!(again.board.to_fen(), epd.board.to_fen());
+
+    assert_eq!(Epd::new(Board::new()).to_epd(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
+}
+
+#[test]
+fn errors() {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -";
+    assert!(matches!(Epd::parse("8/8/8 w"), Err(EpdError::InvalidFen(_))));
+    assert_eq!(Epd::parse(&format!("{} bm e4", start)).err(), Some(EpdError::UnterminatedOperation("bm e4".to_string())));
+    assert_eq!(Epd::parse(&format!("{} id \"open;", start)).err(), Some(EpdError::UnterminatedString));
+    // A full FEN's move counters would otherwise swallow the operation after them
+    assert_eq!(Epd::parse(&format!("{} 0 1 bm e4;", start)).err(), Some(EpdError::InvalidOpcode("0".to_string())));
+    assert_eq!(
+        Epd::parse(&format!("{} dm three;", start)).err(),
+        Some(EpdError::InvalidOperand { opcode: "dm".to_string(), operand: "three".to_string() })
+    );
+    assert_eq!(
+        Epd::parse(&format!("{} bm Nc3 Bc4;", start)).err(),
+        Some(EpdError::IllegalMove {
+            opcode: "bm".to_string(),
+            san: "Bc4".to_string(),
+            error: MoveError::NoMatchingMove(PieceType::Bishop, Square::parse("c4").unwrap()),
+        })
+    );
+}
@@ -0,0 +1,286 @@
+use rustchess::{parse_pgn, Board, Game, GameResult, Move, MoveError, MoveKind, PgnError, PgnTags, PieceColor, PieceType, Square, Termination, TimeControl};
+use std::time::Duration;
+
+fn play(game: &mut Game, moves: &[&str]) {
+    for input in moves {
+        let from = Square::parse(&input[..2]).unwrap();
+        let to = Square::parse(&input[2..]).unwrap();
+        let mv: Move = game.board().create_move(from, to, None);
+        assert!(game.make_move(mv).is_ok(), "{} should be legal", input);
+    }
+}
+
+#[test]
+fn checkmate_ends_the_game() {
+    let mut game = Game::new(Board::new());
+    play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+
+    assert_eq!(game.result(), Some(GameResult::BlackWins));
+    assert_eq!(game.termination(), Some(Termination::Checkmate));
+    assert_eq!(game.moves().len(), 4);
+    assert_eq!(game.positions().len(), 5);
+
+    // No more moves once the game is over, until the mate is taken back
+    let mv = game.board().create_move(Square::parse("a7").unwrap(), Square::parse("a6").unwrap(), None);
+    assert_eq!(game.make_move(mv), Err(MoveError::GameOver));
+    assert!(game.take_back().is_some());
+    assert!(!game.is_over());
+    assert!(game.make_move(mv).is_ok());
+}
+
+#[test]
+fn repetition_can_be_claimed() {
+    let mut game = Game::new(Board::new());
+    assert_eq!(game.claim_draw(), None);
+
+    play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert!(game.claim_draw().is_some());
+    assert_eq!(game.result(), Some(GameResult::Draw));
+    assert_eq!(game.termination(), Some(Termination::Repetition));
+}
+
+#[test]
+fn resignation_and_timeout() {
+    let mut game = Game::new(Board::new());
+    game.resign(PieceColor::White);
+    assert_eq!(game.result(), Some(GameResult::BlackWins));
+    assert_eq!(game.termination(), Some(Termination::Resignation));
+
+    let time_control = TimeControl { initial: Duration::ZERO, increment: Duration::ZERO };
+    let mut game = Game::with_time_control(Board::new(), time_control);
+    assert!(game.check_time());
+    assert_eq!(game.result(), Some(GameResult::BlackWins));
+    assert_eq!(game.termination(), Some(Termination::Timeout));
+}
+
+#[test]
+fn illegal_move_reasons() {
+    let check = |fen: &str, from: &str, to: &str| {
+        let board = Board::from_fen(fen).unwrap();
+        let mv = board.create_move(Square::parse(from).unwrap(), Square::parse(to).unwrap(), None);
+        board.check_move(mv)
+    };
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    assert_eq!(check(start, "e4", "e5"), Err(MoveError::NoPiece(Square::parse("e4").unwrap())));
+    assert_eq!(check(start, "e7", "e5"), Err(MoveError::OpponentPiece(Square::parse("e7").unwrap())));
+    assert_eq!(check(start, "b1", "b3"), Err(MoveError::IllegalPieceMove(PieceType::Knight)));
+    assert_eq!(check(start, "f1", "c4"), Err(MoveError::PathBlocked(Square::parse("e2").unwrap())));
+    assert_eq!(check(start, "d1", "d2"), Err(MoveError::OwnPieceOnTarget(Square::parse("d2").unwrap())));
+    assert_eq!(check(start, "e1", "g1"), Err(MoveError::PathBlocked(Square::parse("f1").unwrap())));
+    assert_eq!(check(start, "e2", "d3"), Err(MoveError::NothingToCapture(Square::parse("d3").unwrap())));
+    assert_eq!(check(start, "e2", "e4"), Ok(()));
+
+    // Pinned bishop, and a king in check that does not get out of it
+    assert_eq!(check("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1", "e2", "d3"), Err(MoveError::LeavesKingInCheck));
+    assert_eq!(check("4k3/4r3/8/8/8/8/3P4/4K3 w - - 0 1", "d2", "d3"), Err(MoveError::StillInCheck));
+
+    // Castling rights gone, and castling through an attacked square
+    assert_eq!(check("4k3/8/8/8/8/8/8/4K2R w - - 0 1", "e1", "g1"), Err(MoveError::CastlingRightsLost));
+    assert_eq!(check("4k3/8/8/8/8/8/5r2/4K2R w K - 0 1", "e1", "g1"), Err(MoveError::CastlingThroughCheck));
+    // A king away from home moving two squares is just an illegal king move
+    assert_eq!(check("4k3/8/8/8/4K3/8/8/8 w - - 0 1", "e4", "g4"), Err(MoveError::IllegalPieceMove(PieceType::King)));
+
+    // The kind a move was built with doesn't make a legal move look unsafe
+    let kinds = |from: &str, to: &str, kind| {
+        let board = Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        board.check_move(Move { from: Square::parse(from).unwrap(), to: Square::parse(to).unwrap(), promotion: None, kind })
+    };
+    assert_eq!(kinds("e4", "d5", MoveKind::Quiet), Ok(()));
+    assert_eq!(kinds("g1", "f3", MoveKind::Capture), Ok(()));
+    assert_eq!(kinds("e4", "e5", MoveKind::DoublePawnPush), Ok(()));
+    let board = Board::from_fen("4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+    let pinned = Move { from: Square::parse("e2").unwrap(), to: Square::parse("d3").unwrap(), promotion: None, kind: MoveKind::Capture };
+    assert_eq!(board.check_move(pinned), Err(MoveError::LeavesKingInCheck));
+
+    let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mv = board.create_move(Square::parse("a7").unwrap(), Square::parse("a8").unwrap(), Some(PieceType::King));
+    assert_eq!(board.check_move(mv), Err(MoveError::InvalidPromotion));
+}
+
+#[test]
+fn move_kind_comes_from_the_squares() {
+    let square = |s| Square::parse(s).unwrap();
+    let mut board = Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+
+    // Moves built by hand with the wrong kind are still played as what they are
+    for (from, to, kind) in [("g1", "f3", MoveKind::Capture), ("e4", "e5", MoveKind::DoublePawnPush)] {
+        let mv = Move { from: square(from), to: square(to), promotion: None, kind };
+        assert!(board.is_valid_move(mv));
+    }
+    let capture = Move { from: square("e4"), to: square("d5"), promotion: None, kind: MoveKind::Quiet };
+    let undo = board.make_move(capture).unwrap();
+    assert_eq!(undo.played_move().kind, MoveKind::Capture);
+    assert_eq!(board.to_fen(), "rnbqkbnr/ppp1pppp/8/3P4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2");
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+    let castle = Move { from: square("e1"), to: square("g1"), promotion: None, kind: MoveKind::Quiet };
+    assert!(board.is_valid_move(castle));
+    let castle = Move { kind: MoveKind::Castle, to: square("h1"), promotion: Some(PieceType::Queen), ..castle };
+    assert!(!board.is_valid_move(castle));
+}
+
+#[test]
+fn san_input() {
+    let parse = |fen: &str, san: &str| {
+        let board = Board::from_fen(fen).unwrap();
+        board.parse_san(san).map(|mv| mv.to_string())
+    };
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    assert_eq!(parse(start, "e4"), Ok("e2e4".to_string()));
+    assert_eq!(parse(start, "Nf3"), Ok("g1f3".to_string()));
+    assert_eq!(parse(start, "Ng1-f3"), Ok("g1f3".to_string()));
+    assert_eq!(parse(start, "e5"), Err(MoveError::IllegalPieceMove(PieceType::Pawn)));
+    assert_eq!(parse(start, "Nd4"), Err(MoveError::NoMatchingMove(PieceType::Knight, Square::parse("d4").unwrap())));
+    assert_eq!(parse(start, "Ne4x"), Err(MoveError::BadSyntax));
+    assert_eq!(parse(start, "Nxf3"), Err(MoveError::NothingToCapture(Square::parse("f3").unwrap())));
+
+    // Captures, disambiguation, promotions and castling
+    let fen = "r3k2r/1P6/8/3p4/4P3/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(parse(fen, "exd5"), Ok("e4d5".to_string()));
+    assert_eq!(parse(fen, "bxa8=N+"), Ok("b7a8n".to_string()));
+    assert_eq!(parse(fen, "b8Q"), Ok("b7b8q".to_string()));
+    assert_eq!(parse(fen, "b8"), Ok("b7b8".to_string()));
+    assert_eq!(parse(fen, "e5=Q"), Err(MoveError::InvalidPromotion));
+    assert_eq!(parse(fen, "O-O"), Ok("e1g1".to_string()));
+    assert_eq!(parse(fen, "0-0-0"), Ok("e1c1".to_string()));
+    assert_eq!(parse("4k3/8/8/8/8/8/8/4K2R w - - 0 1", "O-O"), Err(MoveError::CastlingRightsLost));
+
+    let rooks = "4k3/8/8/8/8/8/4K3/R6R w - - 0 1";
+    assert_eq!(parse(rooks, "Rd1"), Err(MoveError::AmbiguousMove(PieceType::Rook, Square::parse("d1").unwrap())));
+    assert_eq!(parse(rooks, "Rad1"), Ok("a1d1".to_string()));
+    assert_eq!(parse(rooks, "R1d1"), Err(MoveError::AmbiguousMove(PieceType::Rook, Square::parse("d1").unwrap())));
+
+    // Chess960 castling is written the same way
+    let chess960 = "4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1";
+    assert_eq!(parse(chess960, "O-O"), Ok("f1g1".to_string()));
+    assert_eq!(parse(chess960, "O-O-O"), Ok("f1c1".to_string()));
+}
+
+#[test]
+fn san_output() {
+    let san = |fen: &str, input: &str| {
+        let board = Board::from_fen(fen).unwrap();
+        let mv = board.parse_move(input).unwrap();
+        mv.to_san(&board)
+    };
+
+    assert_eq!(san("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3"), "Nf3");
+    assert_eq!(san("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5"), "exd5");
+    assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+    assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1"), "O-O");
+    assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1c1"), "O-O-O");
+    assert_eq!(san("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8n"), "axb8=N");
+    assert_eq!(san("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8"), "a8=Q+");
+
+    // Disambiguation by file, then rank, then both
+    assert_eq!(san("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1"), "Rad1");
+    assert_eq!(san("4k3/R7/8/8/8/8/4K3/R7 w - - 0 1", "a1a4"), "R1a4");
+    assert_eq!(san("6k1/8/8/8/Q2Q4/8/7K/Q7 w - - 0 1", "a4d1"), "Qa4d1");
+
+    // Check and mate
+    assert_eq!(san("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2", "d8h4"), "Qh4#");
+    assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"), "Ra8+");
+
+    let mut game = Game::new(Board::new());
+    play(&mut game, &["e2e4", "e7e5", "g1f3"]);
+    assert_eq!(game.san_moves(), ["e4", "e5", "Nf3"]);
+}
+
+#[test]
+fn pgn_export() {
+    let tags = PgnTags {
+        event: "Test".to_string(),
+        date: "2024.01.02".to_string(),
+        white: "Alice".to_string(),
+        black: "Bob \"B\"".to_string(),
+        ..PgnTags::default()
+    };
+
+    let mut game = Game::new(Board::new());
+    play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+    assert_eq!(
+        game.to_pgn(&tags),
+        "[Event \"Test\"]\n[Site \"?\"]\n[Date \"2024.01.02\"]\n[Round \"?\"]\n[White \"Alice\"]\n\
+         [Black \"Bob \\\"B\\\"\"]\n[Result \"0-1\"]\n[Termination \"normal\"]\n[TimeControl \"-\"]\n\n\
+         1. f3 e5 2. g4 Qh4# {Black wins by checkmate} 0-1\n"
+    );
+
+    // Unfinished games from a set-up position, with long movetext wrapped
+    let fen = "7k/8/8/8/8/8/8/R3K3 b - - 0 30";
+    let time_control = TimeControl { initial: Duration::from_secs(300), increment: Duration::from_secs(2) };
+    let mut game = Game::with_time_control(Board::from_fen(fen).unwrap(), time_control);
+    // The rook tours the a- to d-files while the black king shuffles, so no position repeats
+    let mut rook = "a1".to_string();
+    for file in ["a", "b", "c", "d"] {
+        let ranks: Vec<u32> = if file == "a" || file == "c" { (1..=6).collect() } else { (1..=6).rev().collect() };
+        for rank in ranks {
+            let to = format!("{}{}", file, rank);
+            if to != rook {
+                let king = if game.board().piece_at(Square::parse("h8").unwrap()).is_some() { "h8g8" } else { "g8h8" };
+                play(&mut game, &[king, &format!("{}{}", rook, to)]);
+                rook = to;
+            }
+        }
+    }
+    let pgn = game.to_pgn(&tags);
+    assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"7k/8/8/8/8/8/8/R3K3 b - - 0 30\"]\n"));
+    assert!(pgn.contains("[Result \"*\"]\n"));
+    assert!(pgn.contains("[Termination \"unterminated\"]\n[TimeControl \"300+2\"]\n"));
+    assert!(pgn.contains("\n\n30... Kg8 31. Ra2 Kh8 "));
+    assert!(pgn.lines().all(|line| line.len() <= 80));
+    assert!(pgn.ends_with(" *\n"));
+
+    assert!(Game::new(Board::chess960(0)).to_pgn(&tags).contains("[Variant \"Chess960\"]\n[SetUp \"1\"]\n"));
+}
+
+#[test]
+fn pgn_import() {
+    let pgn = r#"
+% Exported by hand
+[Event "Casual \"blitz\""]
+[White "Alice"]
+[Black "Bob"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {the main line} Nc6 $1 (2... d6 3. d4 (3. Bc4) exd4) 3.Bb5 a6!? ; Ruy Lopez
+4. Ba4 Nf6 5. O-O Be7 1-0
+
+[Event "Mate"]
+[SetUp "1"]
+[FEN "4k3/8/4K3/8/8/8/8/7R w - - 0 40"]
+
+40. Rh8# 1-0
+
+[Event "Unfinished"]
+
+1. d4 d5 *
+"#;
+    let games = parse_pgn(pgn).unwrap();
+    assert_eq!(games.len(), 3);
+
+    assert_eq!(games[0].tag("Event"), Some("Casual \"blitz\""));
+    assert_eq!(games[0].tag("White"), Some("Alice"));
+    assert_eq!(games[0].result, Some(GameResult::WhiteWins));
+    assert_eq!(games[0].game.san_moves(), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7"]);
+
+    assert_eq!(games[1].game.termination(), Some(Termination::Checkmate));
+    assert_eq!(games[1].game.movetext(), "40. Rh8#");
+
+    assert_eq!(games[2].result, None);
+    assert_eq!(games[2].game.moves().len(), 2);
+
+    // A game exported as PGN reads back the same
+    let mut game = Game::new(Board::new());
+    play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+    let games = parse_pgn(&game.to_pgn(&PgnTags::default())).unwrap();
+    assert_eq!(games[0].game.moves(), game.moves());
+    assert_eq!(games[0].result, Some(GameResult::BlackWins));
+
+    let err = parse_pgn("1. e4 e5 2. Ke3 *").err().unwrap();
+    assert_eq!(err.to_string(), "game 1: move 2. Ke3 can't be played: a king can't move like that");
+    assert!(matches!(parse_pgn("1. e4 (1. d4 e5"), Err(PgnError::UnbalancedVariation(1))));
+    assert!(matches!(parse_pgn("1. e4 {comment"), Err(PgnError::UnterminatedComment(1))));
+    assert!(matches!(parse_pgn("[Event Casual]"), Err(PgnError::BadTag(1, _))));
+}
@@ -0,0 +1,178 @@
+use rustchess::{Board, MoveKind, PieceColor, Square};
+
+// Standard positions from the Chess Programming Wiki, with node counts
+// kept small enough for unoptimized test builds
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+// Chess960 positions in Shredder-FEN
+const CHESS960_1: &str = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+const CHESS960_2: &str = "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9";
+const CHESS960_3: &str = "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9";
+
+fn perft(fen: &str, depth: u32) -> u64 {
+    Board::from_fen(fen).unwrap().perft(depth)
+}
+
+#[test]
+fn perft_start_position() {
+    assert_eq!(perft(START, 0), 1);
+    assert_eq!(perft(START, 1), 20);
+    assert_eq!(perft(START, 2), 400);
+    assert_eq!(perft(START, 3), 8902);
+    assert_eq!(perft(START, 4), 197281);
+}
+
+#[test]
+fn perft_kiwipete() {
+    assert_eq!(perft(KIWIPETE, 1), 48);
+    assert_eq!(perft(KIWIPETE, 2), 2039);
+    assert_eq!(perft(KIWIPETE, 3), 97862);
+}
+
+#[test]
+fn perft_position_3() {
+    assert_eq!(perft(POSITION_3, 1), 14);
+    assert_eq!(perft(POSITION_3, 2), 191);
+    assert_eq!(perft(POSITION_3, 3), 2812);
+    assert_eq!(perft(POSITION_3, 4), 43238);
+}
+
+#[test]
+fn perft_position_4() {
+    assert_eq!(perft(POSITION_4, 1), 6);
+    assert_eq!(perft(POSITION_4, 2), 264);
+    assert_eq!(perft(POSITION_4, 3), 9467);
+    assert_eq!(perft(POSITION_4, 4), 422333);
+    assert_eq!(perft(POSITION_4_MIRRORED, 3), 9467);
+}
+
+#[test]
+fn perft_position_5() {
+    assert_eq!(perft(POSITION_5, 1), 44);
+    assert_eq!(perft(POSITION_5, 2), 1486);
+    assert_eq!(perft(POSITION_5, 3), 62379);
+}
+
+#[test]
+fn perft_position_6() {
+    assert_eq!(perft(POSITION_6, 1), 46);
+    assert_eq!(perft(POSITION_6, 2), 2079);
+    assert_eq!(perft(POSITION_6, 3), 89890);
+}
+
+#[test]
+fn perft_chess960() {
+    assert_eq!(perft(CHESS960_1, 3), 12189);
+    assert_eq!(perft(CHESS960_2, 3), 18002);
+    assert_eq!(perft(CHESS960_3, 3), 10471);
+    assert_eq!(perft(&Board::chess960(0).to_fen(), 3), 9006);
+}
+
+#[test]
+fn chess960_start_positions() {
+    assert_eq!(Board::chess960(0).to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+    assert_eq!(Board::chess960(518).to_fen(), Board::new().to_fen());
+
+    let mut setups: Vec<String> = (0..960).map(|n| Board::chess960(n).to_fen()).collect();
+    setups.sort();
+    setups.dedup();
+    assert_eq!(setups.len(), 960);
+}
+
+#[test]
+fn chess960_castling_fen() {
+    // Shredder-FEN files become X-FEN letters for the outermost rooks
+    let board = Board::from_fen(CHESS960_3).unwrap();
+    assert_eq!(board.to_fen(), "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w KQ - 1 9");
+
+    // An inner rook keeps its file
+    let fen = "1r2k1r1/8/8/8/8/8/8/RR2K2R w KBkq - 0 1";
+    assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+}
+
+#[test]
+fn divide_sums_to_perft() {
+    let mut board = Board::from_fen(KIWIPETE).unwrap();
+    let divided = board.divide(2);
+    assert_eq!(divided.len(), 48);
+    assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+}
+
+#[test]
+fn divide_chess960_castling() {
+    // Castling that would read as b1c1, the king's own move, or as the null move g1g1
+    // is written as the king taking its rook, so every line of a divide is distinct
+    for (fen, castle) in [("1k6/8/8/8/8/8/8/RK6 w A - 0 1", "b1a1"), ("4k3/8/8/8/8/8/8/6KR w H - 0 1", "g1h1")] {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mut moves: Vec<String> = board.divide(1).iter().map(|(mv, _)| mv.to_string()).collect();
+        assert!(moves.contains(&castle.to_string()), "{} in {:?}", castle, moves);
+        let count = moves.len();
+        moves.sort();
+        moves.dedup();
+        assert_eq!(moves.len(), count);
+
+        // The written move reads back as the castling
+        let mv = board.parse_move(castle).unwrap();
+        assert_eq!(mv.kind, MoveKind::Castle);
+    }
+}
+
+#[test]
+fn perft_leaves_board_unchanged() {
+    let mut board = Board::from_fen(KIWIPETE).unwrap();
+    let key = board.zobrist_key();
+    board.perft(3);
+    assert_eq!(board.to_fen(), KIWIPETE);
+    assert_eq!(board.zobrist_key(), key);
+}
+
+#[test]
+fn polyglot_keys() {
+    // Reference keys from the Polyglot book format specification
+    let keys = |moves: &[&str]| {
+        let mut board = Board::new();
+        let mut keys = vec![board.zobrist_key()];
+        for input in moves {
+            board.make_move(board.parse_move(input).unwrap()).unwrap();
+            keys.push(board.zobrist_key());
+        }
+        keys
+    };
+
+    // The en passant square only counts once a pawn could take on it
+    let keys_after = keys(&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"]);
+    assert_eq!(
+        keys_after,
+        [
+            0x463b96181691fc9c,
+            0x823c9b50fd114196,
+            0x0756b94461c50fb0,
+            0x662fafb965db29d4,
+            0x22a48b5a8e47ff78,
+            0x652a607ca3f242c1,
+            0x00fdd303c946bdd9,
+        ]
+    );
+
+    let keys_after = keys(&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"]);
+    assert_eq!(keys_after[5], 0x3c8123ea7b067637);
+    assert_eq!(keys_after[7], 0x5c3f9b829b279560);
+}
+
+#[test]
+fn pins_and_checks() {
+    let square = |s| Square::parse(s).unwrap();
+
+    let board = Board::from_fen("4k3/8/8/8/1b6/8/3P4/4K2r w - - 0 1").unwrap();
+    assert_eq!(board.pinned_pieces(PieceColor::White), square("d2").bit());
+    assert_eq!(board.checkers(), square("h1").bit());
+    assert_eq!(board.attackers_of(square("c3")), square("b4").bit() | square("d2").bit());
+
+    // Nothing can capture or block the rook, so only the king has moves
+    assert!(board.legal_moves().iter().all(|mv| mv.from == square("e1")));
+}
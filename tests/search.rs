@@ -0,0 +1,140 @@
+use rustchess::uci::{uci_move, GoParams, Uci};
+use rustchess::xboard::Xboard;
+use rustchess::{Board, ChessAI, GameResult, PieceColor, SearchLimits};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+
+#[test]
+fn iterative_deepening_reports_each_depth() {
+    let board = Board::new();
+    let ai = ChessAI::new(PieceColor::White);
+    let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+
+    let mut depths = Vec::new();
+    let best = ai.search(&board, &limits, |info| depths.push((info.depth, info.best_move, info.nodes)));
+    assert_eq!(depths.iter().map(|&(depth, _, _)| depth).collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(depths.windows(2).all(|pair| pair[0].2 < pair[1].2));
+    assert_eq!(best, Some(depths[2].1));
+    assert_eq!(best, ai.make_move(&board));
+}
+
+#[test]
+fn limits_stop_the_search() {
+    let board = Board::new();
+    let ai = ChessAI::new(PieceColor::White);
+
+    // A stopped search still has a legal move to play
+    let stop = Arc::new(AtomicBool::new(true));
+    let limits = SearchLimits { stop: Some(stop), ..SearchLimits::default() };
+    let best = ai.search(&board, &limits, |_| {}).unwrap();
+    assert!(board.legal_moves().contains(&best));
+
+    let limits = SearchLimits { nodes: Some(1000), ..SearchLimits::default() };
+    let mut last_nodes = 0;
+    ai.search(&board, &limits, |info| last_nodes = info.nodes);
+    assert!(last_nodes > 0 && last_nodes <= 1000);
+
+    let limits = SearchLimits { movetime: Some(Duration::from_millis(50)), ..SearchLimits::default() };
+    let mut deepest = 0;
+    assert!(ai.search(&board, &limits, |info| deepest = info.depth).is_some());
+    assert!(deepest < ChessAI::DEPTH_CAP);
+
+    let mated = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+    assert_eq!(ai.search(&mated, &SearchLimits::default(), |_| {}), None);
+}
+
+#[test]
+fn mate_scores() {
+    // Scores are for the side to move even from an AI made for the other color
+    let board = Board::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+    let ai = ChessAI::new(PieceColor::Black);
+    let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+    let mut infos = Vec::new();
+    let best = ai.search(&board, &limits, |info| infos.push(*info)).unwrap();
+    assert_eq!(best.to_san(&board), "Ra8#");
+    assert_eq!(infos.last().unwrap().mate_in(), Some(1));
+    assert_eq!(infos[1].score, ChessAI::MATE_SCORE - 1);
+
+    // Black is mated in one whatever it plays
+    let board = Board::from_fen("k7/2K5/8/8/8/8/8/1R6 b - - 0 1").unwrap();
+    let mut last = None;
+    ai.search(&board, &limits, |info| last = info.mate_in());
+    assert_eq!(last, Some(-1));
+
+    let quiet = ai.search(&Board::new(), &SearchLimits { depth: Some(1), ..SearchLimits::default() }, |info| last = info.mate_in());
+    assert!(quiet.is_some());
+    assert_eq!(last, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn uci_stop_ends_an_infinite_search() {
+    let mut uci = Uci::new();
+    assert!(uci.handle("position startpos moves e2e4").await);
+    assert!(uci.handle("go infinite").await);
+    assert!(uci.handle("stop").await);
+    assert!(uci.handle("go depth 1").await);
+    uci.wait().await;
+    assert!(!uci.handle("quit").await);
+}
+
+#[test]
+fn go_parameters() {
+    let params = GoParams::parse(&["wtime", "60000", "btime", "30000", "winc", "1000", "movestogo", "20", "bogus"]);
+    assert_eq!(params.wtime, Some(Duration::from_secs(60)));
+    assert_eq!(params.movestogo, Some(20));
+    assert_eq!(params.limits(PieceColor::White).movetime, Some(Duration::from_millis(3750)));
+    assert_eq!(params.limits(PieceColor::Black).movetime, Some(Duration::from_millis(1500)));
+    assert_eq!(params.limits(PieceColor::White).depth, None);
+
+    // Never more than is left on the clock
+    let params = GoParams::parse(&["btime", "40", "binc", "5000"]);
+    assert_eq!(params.limits(PieceColor::Black).movetime, Some(Duration::from_millis(1)));
+
+    assert_eq!(GoParams::parse(&[]).limits(PieceColor::White).depth, Some(ChessAI::MAX_DEPTH));
+    assert_eq!(GoParams::parse(&["depth", "5"]).limits(PieceColor::White).depth, Some(5));
+    let infinite = GoParams::parse(&["infinite"]).limits(PieceColor::White);
+    assert_eq!((infinite.depth, infinite.movetime), (None, None));
+}
+
+#[test]
+fn chess960_castling_in_uci() {
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    let castle = board.parse_san("O-O").unwrap();
+    assert_eq!(uci_move(castle, false), "e1g1");
+    assert_eq!(uci_move(castle, true), "e1h1");
+}
+
+#[tokio::test]
+async fn xboard_session() {
+    let (results, mut moves) = unbounded_channel();
+    let mut xboard = Xboard::new(results);
+
+    for command in ["xboard", "protover 2", "new", "force", "usermove e2e4", "usermove e7e5", "usermove Nf3"] {
+        assert!(xboard.handle(command));
+    }
+    assert_eq!(xboard.game().moves().len(), 3);
+    xboard.handle("undo");
+    assert_eq!(xboard.game().moves().len(), 2);
+    xboard.handle("remove");
+    assert!(xboard.game().moves().is_empty());
+    xboard.handle("usermove e2e5");
+    assert!(xboard.game().moves().is_empty());
+
+    // Times that don't fit a Duration are ignored rather than panicking
+    for command in ["st -1", "st 1e300", "st nan", "level 40 5 -2", "level 40 5 1e300"] {
+        assert!(xboard.handle(command));
+    }
+
+    // The engine finds the mate in one and ends the game
+    xboard.handle("setboard 7k/8/6K1/8/8/8/8/R7 w - - 0 1");
+    xboard.handle("sd 2");
+    xboard.handle("go");
+    let (id, best) = moves.recv().await.unwrap();
+    xboard.search_done(id, best);
+    assert_eq!(xboard.game().san_moves(), ["Ra8#"]);
+    assert_eq!(xboard.game().result(), Some(GameResult::WhiteWins));
+
+    assert!(!xboard.handle("quit"));
+}